@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use egui_notify::Toasts;
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait},
+    },
+    OutputStream, OutputStreamHandle,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{HibikiError, ToastyError};
+
+/// An opened output device, playback stops once this gets dropped
+pub struct AudioOutput {
+    _stream: OutputStream,
+    pub handle: OutputStreamHandle,
+}
+
+impl AudioOutput {
+    /// Opens the device with the given name, `None` opens the system default
+    pub fn open(device_name: Option<&str>) -> Result<Self, HibikiError> {
+        let (stream, handle) = match device_name {
+            Some(name) => {
+                let device = cpal::default_host()
+                    .output_devices()
+                    .map_err(|err| HibikiError::AudioDeviceError(err.to_string()))?
+                    .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+                    .ok_or_else(|| {
+                        HibikiError::AudioDeviceError(format!("'{name}' is not available"))
+                    })?;
+                OutputStream::try_from_device(&device)
+            }
+            None => OutputStream::try_default(),
+        }
+        .map_err(|err| HibikiError::AudioDeviceError(err.to_string()))?;
+        Ok(Self {
+            _stream: stream,
+            handle,
+        })
+    }
+
+    /// Names of all output devices of the default host
+    pub fn device_names() -> Result<Vec<String>, HibikiError> {
+        Ok(cpal::default_host()
+            .output_devices()
+            .map_err(|err| HibikiError::AudioDeviceError(err.to_string()))?
+            .filter_map(|device| device.name().ok())
+            .collect())
+    }
+}
+
+/// Outputs a sound gets played on
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum Route {
+    #[default]
+    Main,
+    Monitor,
+    Both,
+}
+
+impl Route {
+    pub fn main(self) -> bool {
+        matches!(self, Route::Main | Route::Both)
+    }
+
+    pub fn monitor(self) -> bool {
+        matches!(self, Route::Monitor | Route::Both)
+    }
+}
+
+impl Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Route::Main => f.write_str("Main"),
+            Route::Monitor => f.write_str("Monitor"),
+            Route::Both => f.write_str("Main + Monitor"),
+        }
+    }
+}
+
+/// The main output the audience hears and an optional monitor output for local listening
+pub struct Outputs {
+    main: Option<AudioOutput>,
+    /// Chosen main device, `None` follows the system default
+    main_device: Option<String>,
+    monitor: Option<AudioOutput>,
+    /// Chosen monitor device, `None` disables monitoring
+    monitor_device: Option<String>,
+    /// Volume of the monitor relative to the main output
+    pub monitor_volume: f32,
+    /// Volume of the whole scene, applies to both outputs
+    pub master_volume: f32,
+}
+
+impl Outputs {
+    pub fn new(
+        main_device: Option<String>,
+        monitor_device: Option<String>,
+        monitor_volume: f32,
+        toasts: &mut Toasts,
+    ) -> Self {
+        Self {
+            main: Self::open_main(main_device.as_deref(), toasts),
+            main_device,
+            monitor: monitor_device
+                .as_deref()
+                .and_then(|name| AudioOutput::open(Some(name)).handle_toasty(toasts)),
+            monitor_device,
+            monitor_volume,
+            master_volume: 1.0,
+        }
+    }
+
+    fn open_main(device_name: Option<&str>, toasts: &mut Toasts) -> Option<AudioOutput> {
+        AudioOutput::open(device_name)
+            .handle_toasty(toasts)
+            .or_else(|| {
+                // fall back to the system default, but keep the choice for the next start
+                device_name.and_then(|_| AudioOutput::open(None).handle_toasty(toasts))
+            })
+    }
+
+    pub fn main(&self) -> Option<&OutputStreamHandle> {
+        self.main.as_ref().map(|output| &output.handle)
+    }
+
+    pub fn monitor(&self) -> Option<&OutputStreamHandle> {
+        self.monitor.as_ref().map(|output| &output.handle)
+    }
+
+    pub fn main_device(&self) -> Option<&str> {
+        self.main_device.as_deref()
+    }
+
+    pub fn monitor_device(&self) -> Option<&str> {
+        self.monitor_device.as_deref()
+    }
+
+    /// Reopens the main output, everything playing on the old device gets cut
+    pub fn set_main_device(&mut self, device_name: Option<String>, toasts: &mut Toasts) {
+        // some backends can't open a device twice, so close the old stream first
+        self.main = None;
+        self.main = Self::open_main(device_name.as_deref(), toasts);
+        self.main_device = device_name;
+    }
+
+    /// Reopens the monitor output, everything playing on the old device gets cut
+    pub fn set_monitor_device(&mut self, device_name: Option<String>, toasts: &mut Toasts) {
+        self.monitor = None;
+        self.monitor = device_name
+            .as_deref()
+            .and_then(|name| AudioOutput::open(Some(name)).handle_toasty(toasts));
+        self.monitor_device = device_name;
+    }
+}
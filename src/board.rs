@@ -1,82 +1,229 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs, iter,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 
 use eframe::{
-    egui::{self, Sense, Ui, Widget},
-    epaint::Color32,
+    egui::{
+        self, CursorIcon, Event, Key, KeyboardShortcut, Modifiers, Rect, Response, RichText, Sense,
+        Ui,
+    },
+    epaint::{Color32, Stroke},
 };
 use egui_notify::Toasts;
-use rodio::{OutputStreamHandle, Sink, Source};
 
 use crate::{
-    error::ToastyError,
-    scene::{Scene, SceneEntry},
-    sound::{Sound, SoundKind, SoundSource},
+    audio::{AudioOutput, Outputs},
+    bundle,
+    duck::DuckRole,
+    error::{HibikiError, ToastyError},
+    history::{Edit, History},
+    layout::{Layout, Placement},
+    midi::{Midi, MidiMapping, MidiMessage, MidiTarget},
+    pan::PanControl,
+    scene::{self, Scene, SceneEntry, SCENE_VERSION},
+    sound::{Sound, SoundKind, SoundSettings, SoundSource},
     trigger::Trigger,
+    watcher::Watcher,
 };
 
 pub struct Board {
     sounds: Vec<Sound>,
-    stream_handle: OutputStreamHandle,
+    outputs: Outputs,
     selected_controller: Option<usize>,
+    /// Whether the next key press gets bound to the selected sound
+    hotkey_learning: bool,
+    /// Keys held down that sounds are bound to, so their release can't get lost
+    held_keys: HashSet<Key>,
+    midi: Midi,
+    /// Part of the selected sound's mapping that gets bound to the next MIDI message
+    midi_learning: Option<MidiTarget>,
+    /// While arranging, pads can be reordered by dragging instead of playing
+    arranging: bool,
+    dragged_sound: Option<usize>,
+    layout: Layout,
+    scene_name: String,
+    /// Fade-out of manual stops in seconds, zero cuts immediately
+    stop_fade: f32,
+    /// Whether the dialog for relinking missing files is shown
+    relinking: bool,
     scene_path: PathBuf,
+    /// Recently used scenes, the most recent one is first
+    recent_scenes: Vec<PathBuf>,
+    /// The scene as it was last loaded or saved, the board is dirty if it differs
+    saved_scene: Scene,
+    /// Action waiting for the user to save or discard unsaved changes
+    pending_action: Option<PendingAction>,
+    /// Whether the user agreed to close the app
+    closing: bool,
+    /// Whether unsaved changes get written to a sidecar file periodically
+    autosave: bool,
+    last_autosave: Instant,
+    /// Whether this session wrote the sidecar file that is still around
+    autosaved: bool,
+    /// Whether the dialog for restoring an autosave of a previous session is shown
+    recovering: bool,
+    history: History,
+    /// Selected sound and its settings before the edit that is still in progress
+    edit_start: Option<(usize, SoundSettings)>,
+    watcher: Option<Watcher>,
+    /// Modification time of the scene file when we last read or wrote it
+    scene_modified: Option<SystemTime>,
+    /// Whether the dialog for a scene file changed by another application is shown
+    scene_changed: bool,
+    /// Whether one of our menus was open in the last frame, egui closes them with `Escape`
+    menu_open: bool,
+    /// Output devices listed while a device combo box is open, enumerating them is slow
+    device_names: Option<Vec<String>>,
+    /// MIDI ports listed while their combo box is open, each listing opens a MIDI client
+    midi_ports: Option<Vec<String>>,
+}
+
+/// Actions that discard unsaved changes
+enum PendingAction {
+    Open(PathBuf),
+    Reload,
+    Close,
 }
 
+/// Time between two autosaves of a dirty board
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of scenes remembered in the recent scenes menu
+const MAX_RECENT_SCENES: usize = 10;
+
+/// Range of the volume controlled through MIDI, the center of the CC range is unity gain
+const MIDI_MAX_VOLUME: f64 = 2.;
+
 impl Board {
     pub fn new(
         scene_path: PathBuf,
-        stream_handle: OutputStreamHandle,
+        recent_scenes: Vec<PathBuf>,
+        autosave: bool,
+        outputs: Outputs,
+        midi: Midi,
+        watcher: Option<Watcher>,
         toasts: &mut Toasts,
     ) -> Self {
-        Self {
-            sounds: Self::load_sounds(&scene_path, &stream_handle, toasts).unwrap_or_default(),
-            stream_handle,
+        let mut board = Self {
+            sounds: vec![],
+            outputs,
             selected_controller: None,
-            scene_path,
-        }
+            hotkey_learning: false,
+            held_keys: HashSet::new(),
+            midi,
+            midi_learning: None,
+            arranging: false,
+            dragged_sound: None,
+            layout: Layout::default(),
+            scene_name: String::new(),
+            stop_fade: 0.,
+            relinking: false,
+            scene_path: PathBuf::new(),
+            recent_scenes,
+            saved_scene: Scene::default(),
+            pending_action: None,
+            closing: false,
+            autosave,
+            last_autosave: Instant::now(),
+            autosaved: false,
+            recovering: false,
+            history: History::default(),
+            edit_start: None,
+            watcher,
+            scene_modified: None,
+            scene_changed: false,
+            menu_open: false,
+            device_names: None,
+            midi_ports: None,
+        };
+        board.open_scene(scene_path, toasts);
+        board
     }
 
-    fn load_sounds(
-        scene_path: &PathBuf,
-        stream_handle: &OutputStreamHandle,
-        toasts: &mut Toasts,
-    ) -> Option<Vec<Sound>> {
-        let scene = Scene::load(&scene_path).handle_toasty(toasts)?;
-        Some(
-            scene
-                .entries
-                .iter()
-                .filter_map(|entry| {
-                    SoundSource::from_file(entry.sound_path.clone())
-                        .handle_toasty(toasts)
-                        .map(|source| Sound {
-                            kind: entry.controller,
-                            source,
-                            sink: Sink::try_new(&stream_handle).unwrap(),
-                            state: false,
-                            volume: entry.volume,
-                            pan: entry.pan,
-                            color: Color32::from_rgb(
-                                entry.color[0],
-                                entry.color[1],
-                                entry.color[2],
-                            ),
-                        })
+    /// Loads the sounds of a scene, the returned scene keeps only its metadata
+    fn load_sounds(scene_path: &PathBuf, toasts: &mut Toasts) -> Option<(Vec<Sound>, Scene)> {
+        let mut scene = Scene::load(scene_path).handle_toasty(toasts)?;
+        let sounds: Vec<Sound> = std::mem::take(&mut scene.entries)
+            .iter()
+            .filter_map(|entry| {
+                let source = match SoundSource::from_file(entry.sound_path.clone()) {
+                    // keep missing sounds on the board, so they can be relinked
+                    Err(HibikiError::DoesNotExist(path)) => SoundSource::missing(path),
+                    result => result.handle_toasty(toasts)?,
+                };
+                if !source.missing {
+                    // keep broken sounds on the board, so their settings survive
+                    source.validate().handle_toasty(toasts);
+                }
+                Some(Sound {
+                    kind: entry.controller,
+                    name: entry.name.clone().unwrap_or_else(|| source.file_stem()),
+                    icon: entry.icon.clone(),
+                    volume: entry.volume,
+                    pan: entry.pan,
+                    panner: PanControl::new(entry.pan),
+                    color: Color32::from_rgb(entry.color[0], entry.color[1], entry.color[2]),
+                    hotkey: entry.hotkey.as_deref().and_then(Key::from_name),
+                    midi: entry.midi,
+                    size: entry.size,
+                    gap: entry.gap,
+                    route: entry.route,
+                    trim: entry.trim,
+                    fade_in: entry.fade_in,
+                    fade_out: entry.fade_out,
+                    choke_group: entry.choke_group.clone(),
+                    ducking: entry.ducking,
+                    ..Sound::new(source)
                 })
-                .collect(),
-        )
+            })
+            .collect();
+        for (i, sound) in sounds.iter().enumerate() {
+            let Some(key) = sound.hotkey else { continue };
+            if sounds[..i].iter().any(|other| other.hotkey == Some(key)) {
+                toasts.warning(format!(
+                    "Hotkey '{}' is bound to multiple sounds.",
+                    key.name()
+                ));
+            }
+        }
+        let missing = sounds.iter().filter(|sound| sound.source.missing).count();
+        if missing > 0 {
+            toasts.warning(format!("{missing} sound file(s) could not be found."));
+        }
+        Some((sounds, scene))
     }
 
-    fn pack_scene(&self) -> Scene {
+    fn pack_scene(&self, scene_path: &Path) -> Scene {
         Scene {
+            version: SCENE_VERSION,
+            name: self.scene_name.clone(),
+            layout: self.layout,
+            master_volume: self.outputs.master_volume as f64,
             entries: self
                 .sounds
                 .iter()
                 .map(|sound| SceneEntry {
-                    sound_path: sound.source.path.clone(),
+                    sound_path: scene::relative_sound_path(&sound.source.path, scene_path),
                     controller: sound.kind,
                     volume: sound.volume,
                     pan: sound.pan,
                     color: [sound.color.r(), sound.color.g(), sound.color.b()],
+                    name: Some(sound.name.clone()),
+                    icon: sound.icon.clone(),
+                    hotkey: sound.hotkey.map(|key| key.name().to_owned()),
+                    midi: sound.midi,
+                    size: sound.size,
+                    gap: sound.gap,
+                    route: sound.route,
+                    trim: sound.trim,
+                    fade_in: sound.fade_in,
+                    fade_out: sound.fade_out,
+                    choke_group: sound.choke_group.clone(),
+                    ducking: sound.ducking,
                 })
                 .collect(),
         }
@@ -90,8 +237,938 @@ impl Board {
         }
     }
 
+    /// Replaces all sounds, dropping everything that refers to the old ones by index
+    fn set_sounds(&mut self, (sounds, scene): (Vec<Sound>, Scene)) {
+        self.sounds = sounds;
+        self.layout = scene.layout;
+        self.scene_name = scene.name;
+        self.outputs.master_volume = scene.master_volume as f32;
+        self.selected_controller = None;
+        self.hotkey_learning = false;
+        self.midi_learning = None;
+        self.dragged_sound = None;
+        self.relinking = self.sounds.iter().any(|sound| sound.source.missing);
+        self.history.clear();
+        self.edit_start = None;
+    }
+
+    /// Records the edit of the selected sound that is in progress as a single undo step
+    fn flush_edit(&mut self) {
+        let Some((index, before)) = self.edit_start.take() else {
+            return;
+        };
+        if self
+            .sounds
+            .get(index)
+            .is_some_and(|sound| sound.settings() != before)
+        {
+            self.history.record(Edit::Change {
+                index,
+                settings: before,
+            });
+        }
+    }
+
+    /// Coalesces edits of the selected sound until the pointer gets released or text input ends,
+    /// so dragging a knob becomes one undo step
+    fn track_edits(&mut self, ctx: &egui::Context) {
+        let editing = ctx.input(|i| i.pointer.any_down()) || ctx.wants_keyboard_input();
+        let edited = self.edit_start.as_ref().map(|(index, _)| *index);
+        if editing && edited == self.selected_controller {
+            return;
+        }
+        self.flush_edit();
+        self.edit_start = self
+            .selected_controller
+            .and_then(|index| Some((index, self.sounds.get(index)?.settings())));
+    }
+
+    pub fn undo(&mut self) {
+        self.flush_edit();
+        if self
+            .history
+            .undo(&mut self.sounds, &mut self.selected_controller)
+        {
+            self.after_history_step();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.flush_edit();
+        if self
+            .history
+            .redo(&mut self.sounds, &mut self.selected_controller)
+        {
+            self.after_history_step();
+        }
+    }
+
+    /// Drops state that might refer to sounds that moved or went away
+    fn after_history_step(&mut self) {
+        self.dragged_sound = None;
+        self.hotkey_learning = false;
+        self.midi_learning = None;
+    }
+
+    fn handle_undo(&mut self, ctx: &egui::Context) {
+        // text fields have their own undo
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        // checked first, as the shortcut without shift matches with shift held as well
+        let redo = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+        let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+        if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+            self.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+            self.undo();
+        }
+    }
+
+    pub fn recent_scenes(&self) -> &[PathBuf] {
+        &self.recent_scenes
+    }
+
+    /// Switches to the scene at `scene_path` and moves it to the top of the recent scenes
+    fn set_scene_path(&mut self, scene_path: PathBuf) {
+        // the same scene can be reached through different relative paths
+        let scene_path = fs::canonicalize(&scene_path).unwrap_or(scene_path);
+        // new scenes show up once they got saved
+        if scene_path.is_file() {
+            self.recent_scenes.retain(|path| *path != scene_path);
+            self.recent_scenes.insert(0, scene_path.clone());
+            self.recent_scenes.truncate(MAX_RECENT_SCENES);
+        }
+        self.scene_path = scene_path;
+    }
+
+    fn open_scene(&mut self, scene_path: PathBuf, toasts: &mut Toasts) {
+        if let Some(scene) = Self::load_sounds(&scene_path, toasts) {
+            self.set_sounds(scene);
+        }
+        self.set_scene_path(scene_path);
+        self.saved_scene = self.pack_scene(&self.scene_path);
+        self.scene_modified = modified_time(&self.scene_path);
+        self.scene_changed = false;
+        // a leftover autosave means the app didn't get closed properly
+        self.recovering = scene::autosave_path(&self.scene_path).is_file();
+    }
+
+    /// Saves the scene, switching to `scene_path` if that worked
+    fn save_scene(&mut self, scene_path: PathBuf, toasts: &mut Toasts) -> bool {
+        let scene = self.pack_scene(&scene_path);
+        if scene.save(&scene_path).handle_toasty(toasts).is_none() {
+            return false;
+        }
+        self.discard_autosave();
+        self.set_scene_path(scene_path);
+        self.saved_scene = self.pack_scene(&self.scene_path);
+        self.scene_modified = modified_time(&self.scene_path);
+        true
+    }
+
+    /// Reloads sounds whose files got replaced and the scene if it was changed by someone else
+    fn handle_file_changes(&mut self, toasts: &mut Toasts) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        let files = iter::once(self.scene_path.as_path())
+            .chain(self.sounds.iter().map(|sound| sound.source.path.as_path()));
+        watcher.watch(files).handle_toasty(toasts);
+        for path in watcher.changes() {
+            if path == self.scene_path {
+                // our own saves change the file as well
+                if modified_time(&self.scene_path) != self.scene_modified {
+                    if self.is_dirty() {
+                        self.scene_changed = true;
+                    } else {
+                        self.open_scene(self.scene_path.clone(), toasts);
+                        toasts.info("Reloaded the scene, it was changed by another application.");
+                    }
+                }
+                continue;
+            }
+            let mut reloaded = None;
+            for sound in self
+                .sounds
+                .iter_mut()
+                .filter(|sound| sound.source.path == path)
+            {
+                match SoundSource::from_file(path.clone()) {
+                    Ok(source) => {
+                        source.validate().handle_toasty(toasts);
+                        reloaded = Some(source.file_stem());
+                        // voices that are already playing keep the old data
+                        sound.source = source;
+                    }
+                    // files replaced in several steps cause another event once they are back
+                    Err(HibikiError::DoesNotExist(_)) => {}
+                    result => {
+                        result.handle_toasty(toasts);
+                    }
+                }
+            }
+            if let Some(name) = reloaded {
+                toasts.info(format!("Reloaded '{name}'."));
+            }
+        }
+    }
+
+    fn scene_changed_ui(&mut self, ctx: &egui::Context, toasts: &mut Toasts) {
+        if !self.scene_changed {
+            return;
+        }
+        let mut reload = false;
+        let mut keep = false;
+        egui::Window::new("Scene changed")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label("The scene file was changed by another application.");
+                ui.horizontal(|ui| {
+                    reload = ui
+                        .button("Reload")
+                        .on_hover_text("Discards your unsaved changes")
+                        .clicked();
+                    keep = ui
+                        .button("Keep mine")
+                        .on_hover_text("Saving overwrites the other changes")
+                        .clicked();
+                });
+            });
+        if reload {
+            self.open_scene(self.scene_path.clone(), toasts);
+        } else if keep {
+            self.scene_modified = modified_time(&self.scene_path);
+            self.scene_changed = false;
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.pack_scene(&self.scene_path) != self.saved_scene
+    }
+
+    pub fn autosave(&self) -> bool {
+        self.autosave
+    }
+
+    fn discard_autosave(&mut self) {
+        let _ = fs::remove_file(scene::autosave_path(&self.scene_path));
+        self.recovering = false;
+        self.autosaved = false;
+    }
+
+    /// Runs the action right away, or asks first if it would discard unsaved changes
+    fn request(&mut self, action: PendingAction, toasts: &mut Toasts) {
+        if self.is_dirty() {
+            self.pending_action = Some(action);
+        } else {
+            self.run(action, toasts);
+        }
+    }
+
+    fn run(&mut self, action: PendingAction, toasts: &mut Toasts) {
+        match action {
+            PendingAction::Open(scene_path) => self.open_scene(scene_path, toasts),
+            PendingAction::Reload => self.open_scene(self.scene_path.clone(), toasts),
+            PendingAction::Close => self.closing = true,
+        }
+    }
+
+    fn unsaved_changes_ui(&mut self, ctx: &egui::Context, toasts: &mut Toasts) {
+        if ctx.input(|input| input.viewport().close_requested()) && !self.closing && self.is_dirty()
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.pending_action = Some(PendingAction::Close);
+        }
+        if self.pending_action.is_none() {
+            return;
+        }
+        let mut save = false;
+        let mut discard = false;
+        let mut cancel = false;
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label("The scene has unsaved changes.");
+                ui.horizontal(|ui| {
+                    save = ui.button("Save").clicked();
+                    discard = ui.button("Discard").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if cancel {
+            self.pending_action = None;
+        } else if (save && self.save_scene(self.scene_path.clone(), toasts)) || discard {
+            self.discard_autosave();
+            if let Some(action) = self.pending_action.take() {
+                self.run(action, toasts);
+            }
+            if self.closing {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }
+    }
+
+    /// Writes unsaved changes to the sidecar file every [`AUTOSAVE_INTERVAL`]
+    fn autosave_tick(&mut self, ctx: &egui::Context, toasts: &mut Toasts) {
+        let dirty = self.is_dirty();
+        // undoing back to the saved state leaves nothing to recover
+        if self.autosaved && !dirty && !self.recovering {
+            self.discard_autosave();
+        }
+        if !self.autosave || self.recovering || !dirty {
+            self.last_autosave = Instant::now();
+            return;
+        }
+        let elapsed = self.last_autosave.elapsed();
+        if elapsed < AUTOSAVE_INTERVAL {
+            ctx.request_repaint_after(AUTOSAVE_INTERVAL - elapsed);
+            return;
+        }
+        let scene = self.pack_scene(&self.scene_path);
+        if scene
+            .save(&scene::autosave_path(&self.scene_path))
+            .handle_toasty(toasts)
+            .is_some()
+        {
+            self.autosaved = true;
+        }
+        self.last_autosave = Instant::now();
+    }
+
+    fn recovery_ui(&mut self, ctx: &egui::Context, toasts: &mut Toasts) {
+        if !self.recovering {
+            return;
+        }
+        let mut restore = false;
+        let mut discard = false;
+        egui::Window::new("Recover unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label("The scene has unsaved changes from a previous session.");
+                ui.horizontal(|ui| {
+                    restore = ui.button("Restore").clicked();
+                    discard = ui.button("Discard").clicked();
+                });
+            });
+        if restore {
+            // the autosave sits next to the scene, so its sound paths resolve the same way
+            if let Some(scene) = Self::load_sounds(&scene::autosave_path(&self.scene_path), toasts)
+            {
+                self.set_sounds(scene);
+            }
+            self.recovering = false;
+        } else if discard {
+            self.discard_autosave();
+        }
+    }
+
+    /// Searches `dir` recursively for files named like missing sounds and loads them instead
+    fn relink_missing(&mut self, dir: &Path, toasts: &mut Toasts) {
+        let mut wanted: HashMap<OsString, Option<PathBuf>> = self
+            .sounds
+            .iter()
+            .filter(|sound| sound.source.missing)
+            .filter_map(|sound| sound.source.path.file_name())
+            .map(|name| (name.to_owned(), None))
+            .collect();
+        find_files(dir, &mut wanted);
+        let mut relinked = 0;
+        for sound in self.sounds.iter_mut().filter(|sound| sound.source.missing) {
+            let Some(Some(path)) = sound
+                .source
+                .path
+                .file_name()
+                .and_then(|name| wanted.get(name))
+            else {
+                continue;
+            };
+            if let Some(source) = SoundSource::from_file(path.clone()).handle_toasty(toasts) {
+                source.validate().handle_toasty(toasts);
+                sound.source = source;
+                relinked += 1;
+            }
+        }
+        let missing = self
+            .sounds
+            .iter()
+            .filter(|sound| sound.source.missing)
+            .count();
+        if missing == 0 {
+            toasts.success(format!("Relinked {relinked} sound file(s)."));
+            self.relinking = false;
+        } else {
+            toasts.warning(format!(
+                "Relinked {relinked} sound file(s), {missing} are still missing."
+            ));
+        }
+    }
+
+    fn export_bundle(&self, toasts: &mut Toasts) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export scene bundle")
+            .add_filter("Hibiki Bundle", &["hibiki"])
+            .set_file_name("my_scene.hibiki")
+            .save_file()
+        else {
+            return;
+        };
+        let scene = self.pack_scene(&path);
+        let sources = self.sounds.iter().map(|sound| &sound.source);
+        if bundle::export(scene, sources, &path)
+            .handle_toasty(toasts)
+            .is_some()
+            && self.sounds.iter().any(|sound| sound.source.missing)
+        {
+            toasts.warning("Missing sound files were not included in the bundle.");
+        }
+    }
+
+    /// Unpacks a bundle into a chosen folder and opens its scene from there
+    fn import_bundle(&mut self, toasts: &mut Toasts) {
+        let Some(bundle_path) = rfd::FileDialog::new()
+            .set_title("Import scene bundle")
+            .add_filter("Hibiki Bundle", &["hibiki"])
+            .pick_file()
+        else {
+            return;
+        };
+        let Some(dir) = rfd::FileDialog::new()
+            .set_title("Unpack bundle into an empty folder")
+            .pick_folder()
+        else {
+            return;
+        };
+        let Some(scene_path) = bundle::import(&bundle_path, &dir).handle_toasty(toasts) else {
+            return;
+        };
+        self.request(PendingAction::Open(scene_path), toasts);
+    }
+
+    fn relink_ui(&mut self, ctx: &egui::Context, toasts: &mut Toasts) {
+        let mut open = self.relinking;
+        let mut search = false;
+        egui::Window::new("Missing files")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("These sound files could not be found:");
+                for sound in self.sounds.iter().filter(|sound| sound.source.missing) {
+                    ui.label(RichText::new(sound.source.path.display().to_string()).italics());
+                }
+                if ui
+                    .button("Search folder...")
+                    .on_hover_text(
+                        "Looks for files with the same names in a folder and its subfolders",
+                    )
+                    .clicked()
+                {
+                    search = true;
+                }
+            });
+        self.relinking = open;
+        if search {
+            if let Some(dir) = rfd::FileDialog::new()
+                .set_title("Search missing files in")
+                .pick_folder()
+            {
+                self.relink_missing(&dir, toasts);
+            }
+        }
+    }
+
+    pub fn outputs(&self) -> &Outputs {
+        &self.outputs
+    }
+
+    /// Cuts all voices, as the main sinks they play and report their progress on are bound to the old device
+    fn cut_voices(&mut self) {
+        for sound in self.sounds.iter_mut() {
+            sound.stop(None);
+            sound.voices.clear();
+        }
+    }
+
+    fn output_device_ui(&mut self, ui: &mut Ui, toasts: &mut Toasts) {
+        let main = match (self.outputs.main(), self.outputs.main_device()) {
+            (None, _) => RichText::new("No audio device").color(catppuccin_egui::MACCHIATO.red),
+            (Some(_), Some(name)) => RichText::new(name),
+            (Some(_), None) => RichText::new("System default"),
+        };
+        let monitor = match (self.outputs.monitor(), self.outputs.monitor_device()) {
+            (_, None) => RichText::new("Off"),
+            (None, Some(name)) => RichText::new(name).color(catppuccin_egui::MACCHIATO.red),
+            (Some(_), Some(name)) => RichText::new(name),
+        };
+        let mut main_choice = None;
+        let main_open = egui::ComboBox::from_label("Output")
+            .selected_text(main)
+            .show_ui(ui, |ui| {
+                let current = self.outputs.main_device();
+                if ui
+                    .selectable_label(current.is_none(), "System default")
+                    .clicked()
+                {
+                    main_choice = Some(None);
+                }
+                let names = self.device_names.get_or_insert_with(|| {
+                    AudioOutput::device_names()
+                        .handle_toasty(toasts)
+                        .unwrap_or_default()
+                });
+                for name in names.iter() {
+                    if ui
+                        .selectable_label(current == Some(name.as_str()), name)
+                        .clicked()
+                    {
+                        main_choice = Some(Some(name.clone()));
+                    }
+                }
+            })
+            .inner
+            .is_some();
+        let mut monitor_choice = None;
+        let monitor_open = egui::ComboBox::from_label("Monitor")
+            .selected_text(monitor)
+            .show_ui(ui, |ui| {
+                let current = self.outputs.monitor_device();
+                if ui.selectable_label(current.is_none(), "Off").clicked() {
+                    monitor_choice = Some(None);
+                }
+                let names = self.device_names.get_or_insert_with(|| {
+                    AudioOutput::device_names()
+                        .handle_toasty(toasts)
+                        .unwrap_or_default()
+                });
+                for name in names.iter() {
+                    if ui
+                        .selectable_label(current == Some(name.as_str()), name)
+                        .clicked()
+                    {
+                        monitor_choice = Some(Some(name.clone()));
+                    }
+                }
+            })
+            .inner
+            .is_some();
+        // listed again the next time a combo box opens, devices might have been plugged in since
+        if !main_open && !monitor_open {
+            self.device_names = None;
+        }
+        ui.add(
+            egui::DragValue::new(&mut self.outputs.monitor_volume)
+                .clamp_range(0.0..=2.0)
+                .speed(0.01)
+                .prefix("Monitor volume: "),
+        );
+        if let Some(device_name) = main_choice {
+            self.cut_voices();
+            self.outputs.set_main_device(device_name, toasts);
+        }
+        if let Some(device_name) = monitor_choice {
+            // main playback goes on, only the copies on the old monitor device are dropped
+            for sound in self.sounds.iter_mut() {
+                sound.cut_monitor();
+            }
+            self.outputs.set_monitor_device(device_name, toasts);
+        }
+    }
+
+    /// Fade used when stopping sounds by hand, sounds without one fade out with their own fade-out
+    fn stop_fade(&self) -> Option<Duration> {
+        (self.stop_fade > 0.).then(|| Duration::from_secs_f32(self.stop_fade))
+    }
+
+    /// Silences every sound
+    pub fn stop_all(&mut self) {
+        let fade_out = self.stop_fade();
+        for sound in self.sounds.iter_mut() {
+            sound.stop(fade_out.or(sound.fade_out()));
+        }
+    }
+
+    pub fn stop_selected_sound(&mut self) {
+        let fade_out = self.stop_fade();
+        if let Some(sound) = self.selected_controller_mut() {
+            sound.stop(fade_out.or(sound.fade_out()));
+        }
+    }
+
+    /// Removes the selected sound, stopping it if it still plays
+    pub fn remove_selected_sound(&mut self) {
+        self.flush_edit();
+        if let Some(index) = self.selected_controller.take() {
+            let mut sound = self.sounds.remove(index);
+            sound.stop(None);
+            sound.voices.clear();
+            self.history.record(Edit::Insert {
+                index,
+                sounds: vec![sound],
+            });
+            self.hotkey_learning = false;
+            self.midi_learning = None;
+        }
+    }
+
+    /// Inserts a copy of the selected sound right after it and selects the copy.
+    /// Hotkey and MIDI note stay with the original so they don't conflict.
+    pub fn duplicate_selected_sound(&mut self) {
+        self.flush_edit();
+        let Some(index) = self.selected_controller else {
+            return;
+        };
+        let original = &self.sounds[index];
+        let copy = Sound {
+            kind: original.kind,
+            volume: original.volume,
+            pan: original.pan,
+            panner: PanControl::new(original.pan),
+            color: original.color,
+            name: original.name.clone(),
+            icon: original.icon.clone(),
+            size: original.size,
+            route: original.route,
+            trim: original.trim,
+            fade_in: original.fade_in,
+            fade_out: original.fade_out,
+            choke_group: original.choke_group.clone(),
+            ducking: original.ducking,
+            midi: MidiMapping {
+                note: None,
+                ..original.midi
+            },
+            ..Sound::new(original.source.clone())
+        };
+        self.sounds.insert(index + 1, copy);
+        self.history.record(Edit::Remove {
+            index: index + 1,
+            count: 1,
+        });
+        self.selected_controller = Some(index + 1);
+        self.hotkey_learning = false;
+        self.midi_learning = None;
+    }
+
+    fn move_sound(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        self.flush_edit();
+        let sound = self.sounds.remove(from);
+        self.sounds.insert(to, sound);
+        self.history.record(Edit::Move { from: to, to: from });
+        self.selected_controller = self.selected_controller.map(|index| {
+            if index == from {
+                to
+            } else if from < index && index <= to {
+                index - 1
+            } else if to <= index && index < from {
+                index + 1
+            } else {
+                index
+            }
+        });
+    }
+
+    /// Moves the dragged sound onto the pad under the pointer once it gets released
+    fn drop_dragged_sound(&mut self, ui: &Ui, rects: &[Rect]) {
+        let Some(from) = self.dragged_sound else {
+            return;
+        };
+        let pointer = ui.input(|i| i.pointer.interact_pos());
+        let target = pointer.and_then(|pos| rects.iter().position(|rect| rect.contains(pos)));
+        if let Some(to) = target {
+            ui.painter().rect_stroke(
+                rects[to].expand(2.),
+                12.,
+                Stroke::new(2., catppuccin_egui::MACCHIATO.text),
+            );
+        }
+        ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        if ui.input(|i| i.pointer.any_released()) {
+            self.dragged_sound = None;
+            if let Some(to) = target {
+                self.move_sound(from, to);
+            }
+        }
+    }
+
+    pub fn is_learning_hotkey(&self) -> bool {
+        self.hotkey_learning
+    }
+
+    /// Binds the next pressed key to the selected sound, `Escape` cancels
+    pub fn learn_hotkey(&mut self) {
+        self.hotkey_learning = self.selected_controller.is_some();
+    }
+
+    fn handle_hotkeys(&mut self, ui: &Ui, toasts: &mut Toasts) {
+        // don't steal key presses from text fields, releases still have to reach held sounds
+        let typing = ui.ctx().wants_keyboard_input();
+        let (events, focused, keys_down) =
+            ui.input(|i| (i.events.clone(), i.focused, i.keys_down.clone()));
+        for event in events {
+            let Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                ..
+            } = event
+            else {
+                continue;
+            };
+            if !pressed {
+                self.release_hotkey(key);
+                continue;
+            }
+            if typing {
+                continue;
+            }
+            if self.hotkey_learning {
+                self.hotkey_learning = false;
+                if key != Key::Escape {
+                    self.bind_hotkey(key, toasts);
+                }
+                continue;
+            }
+            // panic key, can't be bound to sounds as it cancels learning.
+            // egui closes popups and menus with it too, which shouldn't silence everything.
+            if key == Key::Escape {
+                if !self.menu_open && !ui.ctx().memory(|memory| memory.any_popup_open()) {
+                    self.stop_all();
+                }
+                continue;
+            }
+            let mut started = vec![];
+            for (i, sound) in self.sounds.iter_mut().enumerate() {
+                if sound.hotkey != Some(key) {
+                    continue;
+                }
+                self.held_keys.insert(key);
+                let pressed = Self::sound_pressed(sound, &self.outputs).handle_toasty(toasts);
+                if pressed == Some(true) {
+                    started.push(i);
+                }
+            }
+            self.choke(&started);
+        }
+        // no release arrives for keys let go of while the window isn't focused
+        let lost: Vec<Key> = self
+            .held_keys
+            .iter()
+            .filter(|key| !focused || !keys_down.contains(key))
+            .copied()
+            .collect();
+        for key in lost {
+            self.release_hotkey(key);
+        }
+    }
+
+    fn release_hotkey(&mut self, key: Key) {
+        if !self.held_keys.remove(&key) {
+            return;
+        }
+        for sound in self.sounds.iter_mut() {
+            if sound.hotkey == Some(key) {
+                Self::sound_released(sound);
+            }
+        }
+    }
+
+    fn bind_hotkey(&mut self, key: Key, toasts: &mut Toasts) {
+        let Some(selected) = self.selected_controller else {
+            return;
+        };
+        self.flush_edit();
+        let mut reverts = vec![];
+        for (i, sound) in self.sounds.iter_mut().enumerate() {
+            if i != selected && sound.hotkey == Some(key) {
+                reverts.push(Edit::Change {
+                    index: i,
+                    settings: sound.settings(),
+                });
+                sound.hotkey = None;
+                toasts.warning(format!(
+                    "Hotkey '{}' was moved away from '{}'.",
+                    key.name(),
+                    sound.name
+                ));
+            }
+        }
+        if let Some(sound) = self.sounds.get_mut(selected) {
+            reverts.push(Edit::Change {
+                index: selected,
+                settings: sound.settings(),
+            });
+            sound.hotkey = Some(key);
+        }
+        self.history.record(Edit::Batch(reverts));
+    }
+
+    pub fn midi_learning(&self) -> Option<MidiTarget> {
+        self.midi_learning
+    }
+
+    /// Binds the next matching MIDI message to the selected sound
+    pub fn learn_midi(&mut self, target: MidiTarget) {
+        if self.selected_controller.is_some() {
+            self.midi_learning = Some(target);
+        }
+    }
+
+    fn handle_midi(&mut self, toasts: &mut Toasts) {
+        let messages: Vec<MidiMessage> = self.midi.messages().collect();
+        for message in messages {
+            if let Some(target) = self.midi_learning {
+                if self.learn_midi_message(target, message, toasts) {
+                    self.midi_learning = None;
+                    continue;
+                }
+            }
+            let mut started = vec![];
+            for (i, sound) in self.sounds.iter_mut().enumerate() {
+                match message {
+                    MidiMessage::NoteOn { note } if sound.midi.note == Some(note) => {
+                        let pressed =
+                            Self::sound_pressed(sound, &self.outputs).handle_toasty(toasts);
+                        if pressed == Some(true) {
+                            started.push(i);
+                        }
+                    }
+                    MidiMessage::NoteOff { note } if sound.midi.note == Some(note) => {
+                        Self::sound_released(sound);
+                    }
+                    MidiMessage::ControlChange { controller, value } => {
+                        let value = value as f64 / 127.;
+                        // controllers move continuously, so their changes don't become undo steps
+                        let mut before = self
+                            .edit_start
+                            .as_mut()
+                            .filter(|(index, _)| *index == i)
+                            .map(|(_, before)| before);
+                        if sound.midi.volume_cc == Some(controller) {
+                            sound.volume = value * MIDI_MAX_VOLUME;
+                            if let Some(before) = &mut before {
+                                before.volume = sound.volume;
+                            }
+                        }
+                        if sound.midi.pan_cc == Some(controller) {
+                            sound.pan = value * 2. - 1.;
+                            if let Some(before) = &mut before {
+                                before.pan = sound.pan;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.choke(&started);
+        }
+    }
+
+    /// Returns whether the message was used for learning
+    fn learn_midi_message(
+        &mut self,
+        target: MidiTarget,
+        message: MidiMessage,
+        toasts: &mut Toasts,
+    ) -> bool {
+        let Some(selected) = self.selected_controller else {
+            return false;
+        };
+        match (target, message) {
+            (MidiTarget::Note, MidiMessage::NoteOn { note }) => {
+                self.flush_edit();
+                let mut reverts = vec![];
+                for (i, sound) in self.sounds.iter_mut().enumerate() {
+                    if i != selected && sound.midi.note == Some(note) {
+                        reverts.push(Edit::Change {
+                            index: i,
+                            settings: sound.settings(),
+                        });
+                        sound.midi.note = None;
+                        toasts.warning(format!(
+                            "MIDI note {note} was moved away from '{}'.",
+                            sound.name
+                        ));
+                    }
+                }
+                reverts.push(Edit::Change {
+                    index: selected,
+                    settings: self.sounds[selected].settings(),
+                });
+                self.sounds[selected].midi.note = Some(note);
+                self.history.record(Edit::Batch(reverts));
+                true
+            }
+            (MidiTarget::VolumeCc, MidiMessage::ControlChange { controller, .. }) => {
+                self.sounds[selected].midi.volume_cc = Some(controller);
+                true
+            }
+            (MidiTarget::PanCc, MidiMessage::ControlChange { controller, .. }) => {
+                self.sounds[selected].midi.pan_cc = Some(controller);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn midi_port_ui(&mut self, ui: &mut Ui, toasts: &mut Toasts) {
+        let selected = self.midi.connected_port().unwrap_or("None").to_owned();
+        let mut connect = None;
+        let open = egui::ComboBox::from_label("MIDI")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(self.midi.connected_port().is_none(), "None")
+                    .clicked()
+                {
+                    self.midi.disconnect();
+                }
+                let ports = self
+                    .midi_ports
+                    .get_or_insert_with(|| Midi::ports().handle_toasty(toasts).unwrap_or_default());
+                for port in ports.iter() {
+                    let is_connected = self.midi.connected_port() == Some(port.as_str());
+                    if ui.selectable_label(is_connected, port).clicked() {
+                        connect = Some(port.clone());
+                    }
+                }
+            })
+            .inner
+            .is_some();
+        // listed again the next time it opens, so newly plugged in devices show up
+        if !open {
+            self.midi_ports = None;
+        }
+        if let Some(port) = connect {
+            self.midi.connect(&port).handle_toasty(toasts);
+        }
+    }
+
     pub fn ui(&mut self, ui: &mut Ui, toasts: &mut Toasts) {
-        egui::Window::new("Board").show(ui.ctx(), |ui| {
+        self.handle_file_changes(toasts);
+        self.track_edits(ui.ctx());
+        self.handle_undo(ui.ctx());
+        self.handle_hotkeys(ui, toasts);
+        self.handle_midi(toasts);
+        self.autosave_tick(ui.ctx(), toasts);
+        self.duck();
+        // outside of the window, so playback follows edits and ducking while it is collapsed
+        for sound in self.sounds.iter_mut() {
+            sound.sync(&self.outputs);
+            // the progress needs the length, which some formats only know once decoded
+            sound.source.measure_duration(ui.ctx());
+        }
+        let title = if self.is_dirty() { "Board*" } else { "Board" };
+        // keep the id stable, so the window position survives title changes
+        let window = egui::Window::new(title).id(egui::Id::new("Board"));
+        window.show(ui.ctx(), |ui| {
             ui.horizontal(|ui| {
                 ui.label(self.scene_path.file_name().unwrap().to_str().unwrap());
                 if ui.button("Open").clicked() {
@@ -100,23 +1177,32 @@ impl Board {
                         .add_filter("Hibiki Scene", &["hibiki.ron"])
                         .pick_file()
                     {
-                        if let Some(sounds) = Self::load_sounds(&path, &self.stream_handle, toasts)
-                        {
-                            self.sounds = sounds;
-                        }
-                        self.scene_path = path;
+                        self.request(PendingAction::Open(path), toasts);
                     }
                 }
+                let mut open_recent = None;
+                self.menu_open = ui
+                    .add_enabled_ui(!self.recent_scenes.is_empty(), |ui| {
+                        ui.menu_button("Recent", |ui| {
+                            for path in &self.recent_scenes {
+                                if ui.button(path.display().to_string()).clicked() {
+                                    open_recent = Some(path.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                        })
+                        .inner
+                        .is_some()
+                    })
+                    .inner;
+                if let Some(path) = open_recent {
+                    self.request(PendingAction::Open(path), toasts);
+                }
                 if ui.button("Reload").clicked() {
-                    if let Some(sounds) =
-                        Self::load_sounds(&self.scene_path, &self.stream_handle, toasts)
-                    {
-                        self.sounds = sounds;
-                    }
+                    self.request(PendingAction::Reload, toasts);
                 }
                 if ui.button("Save").clicked() {
-                    let scene = self.pack_scene();
-                    scene.save(&self.scene_path).handle_toasty(toasts);
+                    self.save_scene(self.scene_path.clone(), toasts);
                 }
                 if ui.button("Save as").clicked() {
                     if let Some(path) = rfd::FileDialog::new()
@@ -124,108 +1210,301 @@ impl Board {
                         .set_file_name("my_scene.hibiki.ron")
                         .save_file()
                     {
-                        let scene = self.pack_scene();
-                        scene.save(&path).handle_toasty(toasts);
-                        self.scene_path = path;
+                        self.save_scene(path, toasts);
                     }
                 }
-            });
-            if ui.button("Add Sounds").clicked() {
-                if let Some(paths) = rfd::FileDialog::new()
-                    .add_filter("Sound File", &["mp3", "wav", "flac", "ogg"])
-                    .pick_files()
+                if ui
+                    .button("Export")
+                    .on_hover_text("Save the scene together with its sounds as a single file")
+                    .clicked()
                 {
-                    for path in paths {
-                        if let Some(source) = SoundSource::from_file(path).handle_toasty(toasts) {
-                            self.sounds.push(Sound {
-                                kind: SoundKind::Trigger,
-                                source,
-                                sink: Sink::try_new(&self.stream_handle).unwrap(),
-                                state: false,
-                                volume: 1.0,
-                                pan: 0.0,
-                                color: catppuccin_egui::MACCHIATO.surface1,
-                            });
-                        }
-                    }
+                    self.export_bundle(toasts);
                 }
-            }
+                if ui.button("Import").clicked() {
+                    self.import_bundle(toasts);
+                }
+                self.midi_port_ui(ui, toasts);
+                let missing = self
+                    .sounds
+                    .iter()
+                    .filter(|sound| sound.source.missing)
+                    .count();
+                if missing > 0
+                    && ui
+                        .button(
+                            RichText::new(format!("Relink {missing} missing"))
+                                .color(catppuccin_egui::MACCHIATO.red),
+                        )
+                        .clicked()
+                {
+                    self.relinking = true;
+                }
+            });
             ui.horizontal(|ui| {
-                // TODO: make this a grid instead of horizontal
-                // TODO: can we make the buttons more easily distinguishable?
-                for (i, sound) in self.sounds.iter_mut().enumerate() {
-                    if Self::sound_trigger(ui, sound, &self.stream_handle) {
-                        if self.selected_controller.is_some_and(|index| index == i) {
-                            self.selected_controller = None;
-                        } else {
-                            self.selected_controller = Some(i);
+                ui.label("Scene name: ");
+                ui.text_edit_singleline(&mut self.scene_name);
+                ui.add(
+                    egui::DragValue::new(&mut self.outputs.master_volume)
+                        .clamp_range(0.0..=2.0)
+                        .speed(0.01)
+                        .prefix("Master volume: "),
+                );
+                ui.checkbox(&mut self.autosave, "Autosave")
+                    .on_hover_text(format!(
+                        "Writes unsaved changes next to the scene every {} seconds",
+                        AUTOSAVE_INTERVAL.as_secs()
+                    ));
+            });
+            ui.horizontal(|ui| self.output_device_ui(ui, toasts));
+            ui.horizontal(|ui| {
+                if ui.button("Add Sounds").clicked() {
+                    if let Some(paths) = rfd::FileDialog::new()
+                        .add_filter("Sound File", &["mp3", "wav", "flac", "ogg"])
+                        .pick_files()
+                    {
+                        self.flush_edit();
+                        let index = self.sounds.len();
+                        for path in paths {
+                            if let Some(source) = SoundSource::from_file(path)
+                                .and_then(|source| source.validate().map(|_| source))
+                                .handle_toasty(toasts)
+                            {
+                                self.sounds.push(Sound::new(source));
+                            }
+                        }
+                        let count = self.sounds.len() - index;
+                        if count > 0 {
+                            self.history.record(Edit::Remove { index, count });
                         }
                     }
                 }
+                if ui
+                    .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
+                    .on_hover_text("Ctrl+Z")
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
+                    .on_hover_text("Ctrl+Shift+Z")
+                    .clicked()
+                {
+                    self.redo();
+                }
+                if ui.toggle_value(&mut self.arranging, "Arrange").changed() {
+                    self.dragged_sound = None;
+                }
+                ui.label("Columns: ");
+                ui.add(egui::DragValue::new(&mut self.layout.columns).clamp_range(1..=32));
             });
+            ui.horizontal(|ui| {
+                if ui
+                    .button(RichText::new("Stop all").color(catppuccin_egui::MACCHIATO.red))
+                    .on_hover_text("Esc")
+                    .clicked()
+                {
+                    self.stop_all();
+                }
+                ui.label("Stop fade: ");
+                ui.add(
+                    egui::DragValue::new(&mut self.stop_fade)
+                        .clamp_range(0.0..=5.0)
+                        .speed(0.01)
+                        .suffix(" s"),
+                );
+            });
+            // TODO: can we make the buttons more easily distinguishable?
+            let placements = self
+                .layout
+                .arrange(self.sounds.iter().map(|sound| (sound.size, sound.gap)));
+            let (grid, _) = ui.allocate_exact_size(self.layout.size(&placements), Sense::hover());
+            if self.arranging {
+                // show the whole grid so empty slots are visible
+                for row in 0..self.layout.rows(&placements) {
+                    for column in 0..self.layout.columns.max(1) {
+                        let cell = Placement {
+                            column,
+                            row,
+                            columns: 1,
+                            rows: 1,
+                        };
+                        ui.painter().rect_stroke(
+                            cell.rect(grid.min),
+                            10.,
+                            Stroke::new(1., catppuccin_egui::MACCHIATO.surface1),
+                        );
+                    }
+                }
+            }
+            let stop_fade = self.stop_fade();
+            let mut rects = Vec::with_capacity(self.sounds.len());
+            let mut started = vec![];
+            for (i, (sound, placement)) in self.sounds.iter_mut().zip(&placements).enumerate() {
+                let rect = placement.rect(grid.min);
+                let (trigger, pressed) =
+                    Self::sound_trigger(ui, rect, sound, &self.outputs, !self.arranging, toasts);
+                if pressed {
+                    started.push(i);
+                }
+                if trigger.middle_clicked() {
+                    sound.stop(stop_fade.or(sound.fade_out()));
+                }
+                if self.arranging && trigger.interact(Sense::drag()).drag_started() {
+                    self.dragged_sound = Some(i);
+                }
+                rects.push(rect);
+                if trigger.secondary_clicked() {
+                    if self.selected_controller.is_some_and(|index| index == i) {
+                        self.selected_controller = None;
+                    } else {
+                        self.selected_controller = Some(i);
+                    }
+                    self.hotkey_learning = false;
+                    self.midi_learning = None;
+                }
+            }
+            self.choke(&started);
+            self.drop_dragged_sound(ui, &rects);
         });
+        self.relink_ui(ui.ctx(), toasts);
+        self.recovery_ui(ui.ctx(), toasts);
+        self.scene_changed_ui(ui.ctx(), toasts);
+        self.unsaved_changes_ui(ui.ctx(), toasts);
+        if self.sounds.iter().any(Sound::is_playing) {
+            // keep the progress of playing pads moving
+            ui.ctx().request_repaint_after(Duration::from_millis(30));
+        }
     }
 
-    fn sound_trigger(ui: &mut Ui, sound: &mut Sound, stream_handle: &OutputStreamHandle) -> bool {
-        let trigger = Trigger { color: sound.color }.ui(ui);
+    fn sound_trigger(
+        ui: &mut Ui,
+        rect: Rect,
+        sound: &mut Sound,
+        outputs: &Outputs,
+        playable: bool,
+        toasts: &mut Toasts,
+    ) -> (Response, bool) {
+        let trigger = ui
+            .put(
+                rect,
+                Trigger {
+                    color: sound.color,
+                    size: rect.size(),
+                    label: &sound.name,
+                    icon: &sound.icon,
+                    status: sound.status(),
+                },
+            )
+            .on_hover_text(sound.source.path.display().to_string());
+        if !playable {
+            return (trigger, false);
+        }
+        let mut started = None;
         match sound.kind {
-            SoundKind::Trigger if trigger.clicked() => {
-                let source = sound.source.decoder();
-                stream_handle
-                    .play_raw(source.convert_samples().amplify(sound.volume as f32))
-                    .unwrap();
-            }
-            SoundKind::CutItself if trigger.clicked() => {
-                let source = sound.source.decoder();
-                sound.sink.clear();
-                sound.sink.append(source);
-                sound.sink.play();
-            }
             // we need to use Sense::drag via interact here so we also trigger through a click without drag movement
-            SoundKind::Hold if trigger.interact(Sense::drag()).drag_started() => {
-                let source = sound.source.decoder();
-                sound.sink.clear();
-                sound.sink.append(source);
-                sound.sink.play();
-            }
-            SoundKind::Hold if trigger.interact(Sense::drag()).drag_released() => {
-                sound.sink.stop();
-            }
-            SoundKind::HoldRepeat if trigger.interact(Sense::drag()).drag_started() => {
-                let source = sound.source.decoder();
-                sound.sink.clear();
-                sound.sink.append(source.repeat_infinite());
-                sound.sink.play();
-            }
-            SoundKind::HoldRepeat if trigger.interact(Sense::drag()).drag_released() => {
-                sound.sink.stop();
-            }
-            SoundKind::Toggle if trigger.clicked() => {
-                if sound.state && !sound.sink.empty() {
-                    sound.state = false;
-                    sound.sink.clear();
-                } else {
-                    sound.state = true;
-                    let source = sound.source.decoder();
-                    sound.sink.clear();
-                    sound.sink.append(source);
-                    sound.sink.play();
-                }
-            }
-            SoundKind::ToggleRepeat if trigger.clicked() => {
-                if sound.state {
-                    sound.state = false;
-                    sound.sink.clear();
-                } else {
-                    sound.state = true;
-                    let source = sound.source.decoder();
-                    sound.sink.clear();
-                    sound.sink.append(source.repeat_infinite());
-                    sound.sink.play();
+            SoundKind::Hold | SoundKind::HoldRepeat => {
+                let drag = trigger.interact(Sense::drag());
+                if drag.drag_started() {
+                    started = Self::sound_pressed(sound, outputs).handle_toasty(toasts);
+                } else if drag.drag_released() {
+                    Self::sound_released(sound);
                 }
             }
+            _ if trigger.clicked() => {
+                started = Self::sound_pressed(sound, outputs).handle_toasty(toasts);
+            }
             _ => {}
         }
-        trigger.secondary_clicked()
+        (trigger, started == Some(true))
+    }
+
+    /// Starts the sound as its kind demands, for both pointer and key presses.
+    /// Returns whether a new voice got started.
+    fn sound_pressed(sound: &mut Sound, outputs: &Outputs) -> Result<bool, HibikiError> {
+        let started = match sound.kind {
+            SoundKind::Trigger => sound.start(outputs, false),
+            SoundKind::CutItself | SoundKind::Hold => {
+                sound.stop(sound.fade_out());
+                sound.start(outputs, false)
+            }
+            SoundKind::HoldRepeat => {
+                sound.stop(sound.fade_out());
+                sound.start(outputs, true)
+            }
+            SoundKind::Toggle | SoundKind::ToggleRepeat => {
+                if sound.state && sound.is_playing() {
+                    sound.stop(sound.fade_out());
+                    return Ok(false);
+                }
+                sound.stop(sound.fade_out());
+                sound.state = true;
+                sound.start(outputs, sound.kind == SoundKind::ToggleRepeat)
+            }
+        };
+        started.map(|()| true)
+    }
+
+    /// Stops the other sounds in the choke groups of the sounds that just started,
+    /// the last started sound of a group keeps playing
+    fn choke(&mut self, started: &[usize]) {
+        for (i, &index) in started.iter().enumerate() {
+            let group = &self.sounds[index].choke_group;
+            if group.is_empty()
+                || started[i + 1..]
+                    .iter()
+                    .any(|&later| self.sounds[later].choke_group == *group)
+            {
+                continue;
+            }
+            let group = group.clone();
+            for (j, sound) in self.sounds.iter_mut().enumerate() {
+                if j != index && sound.choke_group == group {
+                    sound.stop(sound.fade_out());
+                }
+            }
+        }
+    }
+
+    /// Lowers the volume of the ducked sounds while any ducking sound plays
+    fn duck(&self) {
+        let ducking = self
+            .sounds
+            .iter()
+            .any(|sound| sound.ducking.role == DuckRole::Ducks && sound.is_playing());
+        // the playing sources ramp towards the new volume on their own
+        for sound in self.sounds.iter() {
+            sound.ducker.set(&sound.ducking, ducking);
+        }
+    }
+
+    /// Stops holding sounds, the other kinds only react to presses
+    fn sound_released(sound: &mut Sound) {
+        if let SoundKind::Hold | SoundKind::HoldRepeat = sound.kind {
+            sound.stop(sound.fade_out());
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Fills in the first file found below `dir` for each wanted file name, symlinked dirs are skipped
+fn find_files(dir: &Path, wanted: &mut HashMap<OsString, Option<PathBuf>>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            find_files(&entry.path(), wanted);
+        } else if let Some(found) = wanted.get_mut(&entry.file_name()) {
+            found.get_or_insert_with(|| entry.path());
+        }
     }
 }
@@ -0,0 +1,84 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{error::HibikiError, scene::Scene, sound::SoundSource};
+
+/// Name of the scene inside a bundle, its sounds are stored next to it in `sounds/`
+const BUNDLE_SCENE: &str = "scene.hibiki.ron";
+
+/// Writes the scene together with the audio of its sounds into a single zip archive.
+/// `sources` has to match the scene's entries, missing files keep their original absolute path.
+pub fn export<'a>(
+    mut scene: Scene,
+    sources: impl Iterator<Item = &'a SoundSource>,
+    bundle_path: &Path,
+) -> Result<(), HibikiError> {
+    let file = File::create(bundle_path).map_err(HibikiError::InternalError)?;
+    let mut zip = ZipWriter::new(file);
+    // audio is compressed already
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut archived: HashMap<PathBuf, String> = HashMap::new();
+    let mut names = HashSet::new();
+    for (entry, source) in scene.entries.iter_mut().zip(sources) {
+        if source.missing {
+            // the entry's path is relative to the bundle, which means nothing once unpacked elsewhere
+            entry.sound_path = source.path.clone();
+            continue;
+        }
+        if let Some(name) = archived.get(&source.path) {
+            entry.sound_path = PathBuf::from(name);
+            continue;
+        }
+        let file_name = source
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // different folders can contain files with the same name
+        let mut name = format!("sounds/{file_name}");
+        let mut copy = 1;
+        while names.contains(&name) {
+            name = format!("sounds/{copy}-{file_name}");
+            copy += 1;
+        }
+        zip.start_file(name.as_str(), stored)
+            .map_err(HibikiError::BundleError)?;
+        zip.write_all(&source.data)
+            .map_err(HibikiError::InternalError)?;
+        entry.sound_path = PathBuf::from(&name);
+        names.insert(name.clone());
+        archived.insert(source.path.clone(), name);
+    }
+    zip.start_file(BUNDLE_SCENE, FileOptions::default())
+        .map_err(HibikiError::BundleError)?;
+    scene.write(&mut zip)?;
+    zip.finish().map_err(HibikiError::BundleError)?;
+    Ok(())
+}
+
+/// Unpacks the bundle into the empty directory `dir` and returns the path of the unpacked scene
+pub fn import(bundle_path: &Path, dir: &Path) -> Result<PathBuf, HibikiError> {
+    // extracting overwrites files without asking
+    if fs::read_dir(dir)
+        .map_err(HibikiError::InternalError)?
+        .next()
+        .is_some()
+    {
+        return Err(HibikiError::DirectoryNotEmpty(dir.to_owned()));
+    }
+    let file = File::open(bundle_path).map_err(HibikiError::InternalError)?;
+    let mut zip = ZipArchive::new(file).map_err(HibikiError::BundleError)?;
+    // fails on entries that would end up outside of dir
+    zip.extract(dir).map_err(HibikiError::BundleError)?;
+    let scene_path = dir.join(BUNDLE_SCENE);
+    if !scene_path.is_file() {
+        return Err(HibikiError::DoesNotExist(scene_path));
+    }
+    Ok(scene_path)
+}
@@ -0,0 +1,123 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use rodio::{Sink, Source};
+
+use crate::{
+    audio::AudioOutput,
+    error::HibikiError,
+    fade::{FadeControl, Fading},
+    pan::{PanControl, Panned},
+    scene::{Scene, SceneEntry},
+    sound::SoundSource,
+};
+
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Scene to open, defaults to `scene.hibiki.ron` in the working directory
+    pub scene: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Plays the trimmed part of a single pad once without opening a window
+    Play {
+        scene: PathBuf,
+        /// Name shown on the pad
+        pad: String,
+        /// Output device, defaults to the system default
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Checks that every sound of a scene exists and can be decoded
+    Validate { scene: PathBuf },
+}
+
+impl Command {
+    pub fn run(self) -> ExitCode {
+        let result = match self {
+            Command::Play { scene, pad, device } => play(&scene, &pad, device.as_deref()),
+            Command::Validate { scene } => validate(&scene),
+        };
+        match result {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+/// Unlike the UI, which starts a new scene, fails if there is no scene at the path
+fn load_scene(scene_path: &Path) -> Result<Scene, HibikiError> {
+    if !scene_path.exists() {
+        return Err(HibikiError::DoesNotExist(scene_path.to_owned()));
+    }
+    Scene::load(&scene_path.to_owned())
+}
+
+fn pad_name(entry: &SceneEntry) -> String {
+    entry.name.clone().unwrap_or_else(|| {
+        entry
+            .sound_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    })
+}
+
+fn play(scene_path: &Path, pad: &str, device: Option<&str>) -> Result<ExitCode, HibikiError> {
+    let scene = load_scene(scene_path)?;
+    let Some(entry) = scene.entries.iter().find(|entry| pad_name(entry) == pad) else {
+        eprintln!("There is no pad named '{pad}', the scene contains:");
+        for entry in scene.entries.iter() {
+            eprintln!("  {}", pad_name(entry));
+        }
+        return Ok(ExitCode::FAILURE);
+    };
+    let source = SoundSource::from_file(entry.sound_path.clone())?;
+    let output = AudioOutput::open(device)?;
+    let sink = Sink::try_new(&output.handle).map_err(HibikiError::PlaybackError)?;
+    sink.set_volume((entry.volume * scene.master_volume) as f32);
+    let trimmed = entry
+        .trim
+        .apply(|| Ok(source.decoder()?.convert_samples::<f32>()), false)?;
+    sink.append(Fading::new(
+        Panned::new(trimmed, PanControl::new(entry.pan)),
+        FadeControl::default(),
+        Duration::from_secs_f64(entry.fade_in.max(0.)),
+    ));
+    sink.sleep_until_end();
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(scene_path: &Path) -> Result<ExitCode, HibikiError> {
+    let scene = load_scene(scene_path)?;
+    let mut failed = 0;
+    for entry in scene.entries.iter() {
+        let result =
+            SoundSource::from_file(entry.sound_path.clone()).and_then(|source| source.validate());
+        if let Err(err) = result {
+            eprintln!("{}: {err}", pad_name(entry));
+            failed += 1;
+        }
+    }
+    println!(
+        "{} of {} sounds are playable.",
+        scene.entries.len() - failed,
+        scene.entries.len()
+    );
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
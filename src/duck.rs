@@ -0,0 +1,257 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+
+/// Part a sound plays in ducking
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum DuckRole {
+    #[default]
+    None,
+    /// Lowers the volume of the ducked sounds while playing
+    Ducks,
+    /// Gets quieter while any ducking sound plays
+    Ducked,
+}
+
+impl Display for DuckRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuckRole::None => f.write_str("None"),
+            DuckRole::Ducks => f.write_str("Ducks others"),
+            DuckRole::Ducked => f.write_str("Gets ducked"),
+        }
+    }
+}
+
+/// How a sound ducks, the times and depth are used by the ducked sounds
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ducking {
+    pub role: DuckRole,
+    /// Part of the volume that gets taken away, `1.0` silences the sound
+    pub depth: f64,
+    /// Seconds it takes to duck
+    pub attack: f64,
+    /// Seconds it takes to come back to full volume
+    pub release: f64,
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Self {
+            role: DuckRole::None,
+            depth: 0.7,
+            attack: 0.1,
+            release: 0.5,
+        }
+    }
+}
+
+impl Ducking {
+    /// Volume factor the gain of a ducked sound moves towards
+    pub fn target(&self, ducked: bool) -> f64 {
+        if ducked {
+            1. - self.depth.clamp(0., 1.)
+        } else {
+            1.
+        }
+    }
+
+    /// Moves the gain of a ducked sound `dt` seconds towards its target
+    pub fn step(&self, gain: f64, ducked: bool, dt: f64) -> f64 {
+        let depth = self.depth.clamp(0., 1.);
+        let target = self.target(ducked);
+        let time = if ducked { self.attack } else { self.release };
+        if time <= 0. {
+            return target;
+        }
+        // the whole way between both volumes takes `time`, leftovers of a larger depth catch up as fast
+        let step = depth.max((target - gain).abs()) * dt / time;
+        if gain < target {
+            (gain + step).min(target)
+        } else {
+            (gain - step).max(target)
+        }
+    }
+}
+
+/// Ducking settings of a sound and whether it is ducked right now,
+/// shared with all of its playing sources so they ramp their gain themselves
+#[derive(Clone, Default)]
+pub struct DuckControl(Arc<DuckState>);
+
+#[derive(Default)]
+struct DuckState {
+    /// Whether the sound gets ducked by others at all
+    enabled: AtomicBool,
+    ducked: AtomicBool,
+    depth: AtomicU64,
+    attack: AtomicU64,
+    release: AtomicU64,
+}
+
+impl DuckControl {
+    pub fn set(&self, ducking: &Ducking, ducked: bool) {
+        let state = &self.0;
+        state
+            .enabled
+            .store(ducking.role == DuckRole::Ducked, Ordering::Relaxed);
+        state.ducked.store(ducked, Ordering::Relaxed);
+        state
+            .depth
+            .store(ducking.depth.to_bits(), Ordering::Relaxed);
+        state
+            .attack
+            .store(ducking.attack.to_bits(), Ordering::Relaxed);
+        state
+            .release
+            .store(ducking.release.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Settings and whether the sound is ducked, `None` if it doesn't get ducked
+    fn get(&self) -> Option<(Ducking, bool)> {
+        let state = &self.0;
+        if !state.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let ducking = Ducking {
+            role: DuckRole::Ducked,
+            depth: f64::from_bits(state.depth.load(Ordering::Relaxed)),
+            attack: f64::from_bits(state.attack.load(Ordering::Relaxed)),
+            release: f64::from_bits(state.release.load(Ordering::Relaxed)),
+        };
+        Some((ducking, state.ducked.load(Ordering::Relaxed)))
+    }
+}
+
+/// Applies the ducking gain, ramping it sample by sample when a sound starts or stops ducking it
+pub struct Ducked<S> {
+    input: S,
+    control: DuckControl,
+    gain: f64,
+}
+
+impl<S: Source<Item = f32>> Ducked<S> {
+    pub fn new(input: S, control: DuckControl) -> Self {
+        // voices start at the right volume
+        let gain = control
+            .get()
+            .map_or(1., |(ducking, ducked)| ducking.target(ducked));
+        Self {
+            input,
+            control,
+            gain,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Ducked<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        self.gain = match self.control.get() {
+            Some((ducking, ducked)) => {
+                let samples_per_second =
+                    self.input.sample_rate() as f64 * self.input.channels() as f64;
+                ducking.step(self.gain, ducked, 1. / samples_per_second)
+            }
+            None => 1.,
+        };
+        Some(sample * self.gain as f32)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Ducked<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn ducked() -> Ducking {
+        Ducking {
+            role: DuckRole::Ducked,
+            depth: 0.5,
+            attack: 1.,
+            release: 2.,
+        }
+    }
+
+    #[test]
+    fn target_takes_away_the_depth() {
+        assert_eq!(ducked().target(true), 0.5);
+        assert_eq!(ducked().target(false), 1.);
+        let too_deep = Ducking {
+            depth: 1.5,
+            ..ducked()
+        };
+        assert_eq!(too_deep.target(true), 0.);
+    }
+
+    #[test]
+    fn step_takes_attack_and_release_for_the_whole_way() {
+        let ducking = ducked();
+        assert_eq!(ducking.step(1., true, 0.5), 0.75);
+        assert_eq!(ducking.step(0.75, true, 0.5), 0.5);
+        // stops at the target
+        assert_eq!(ducking.step(0.5, true, 0.5), 0.5);
+        assert_eq!(ducking.step(0.5, false, 1.), 0.75);
+        assert_eq!(ducking.step(0.75, false, 10.), 1.);
+    }
+
+    #[test]
+    fn step_without_time_jumps_to_the_target() {
+        let ducking = Ducking {
+            attack: 0.,
+            ..ducked()
+        };
+        assert_eq!(ducking.step(1., true, 0.01), 0.5);
+    }
+
+    #[test]
+    fn sources_ramp_per_sample() {
+        let control = DuckControl::default();
+        control.set(&ducked(), false);
+        // 4 samples per second, so the attack takes 4 samples
+        let input = SamplesBuffer::new(1, 4, vec![1.; 6]);
+        let source = Ducked::new(input, control.clone());
+        control.set(&ducked(), true);
+        let samples: Vec<f32> = source.collect();
+        assert_eq!(samples, [0.875, 0.75, 0.625, 0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn sources_start_ducked() {
+        let control = DuckControl::default();
+        control.set(&ducked(), true);
+        let input = SamplesBuffer::new(1, 4, vec![1.; 2]);
+        let samples: Vec<f32> = Ducked::new(input, control).collect();
+        assert_eq!(samples, [0.5, 0.5]);
+    }
+}
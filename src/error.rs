@@ -1,14 +1,57 @@
-use std::{io, path::PathBuf};
+use std::{fmt::Display, io, path::PathBuf};
 
 use egui_notify::Toasts;
+use rodio::{decoder::DecoderError, PlayError};
 use ron::error::SpannedError;
+use zip::result::ZipError;
 
+#[derive(Debug)]
 pub enum HibikiError {
     DoesNotExist(PathBuf),
     NotAFile(PathBuf),
+    UndecodableAudio(PathBuf, DecoderError),
     InternalError(io::Error),
     BrokenScene(SpannedError),
+    UnsupportedSceneVersion(u32),
     SceneSerialize(ron::Error),
+    BundleError(ZipError),
+    DirectoryNotEmpty(PathBuf),
+    MidiError(String),
+    PlaybackError(PlayError),
+    AudioDeviceError(String),
+    NoAudioDevice,
+    WatchError(notify::Error),
+}
+
+impl Display for HibikiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HibikiError::DoesNotExist(path) => write!(f, "File at '{path:?}' does not exist."),
+            HibikiError::NotAFile(path) => write!(f, "'{path:?}' is not a file."),
+            HibikiError::UndecodableAudio(path, err) => {
+                write!(f, "Can't decode '{path:?}': {err}")
+            }
+            HibikiError::InternalError(err) => write!(f, "Internal error occured: {err:?}"),
+            HibikiError::BrokenScene(err) => write!(f, "Broken scene file: {err:?}"),
+            HibikiError::UnsupportedSceneVersion(version) => write!(
+                f,
+                "Scene format version {version} is not supported, please update Hibiki."
+            ),
+            HibikiError::SceneSerialize(err) => write!(f, "Couldn't save scene: {err:?}"),
+            HibikiError::BundleError(err) => write!(f, "Broken bundle: {err}"),
+            HibikiError::DirectoryNotEmpty(path) => {
+                write!(
+                    f,
+                    "'{path:?}' is not empty, bundles can only be unpacked into empty folders."
+                )
+            }
+            HibikiError::MidiError(err) => write!(f, "MIDI error: {err}"),
+            HibikiError::PlaybackError(err) => write!(f, "Couldn't play sound: {err}"),
+            HibikiError::AudioDeviceError(err) => write!(f, "Audio device error: {err}"),
+            HibikiError::NoAudioDevice => f.write_str("No audio device available."),
+            HibikiError::WatchError(err) => write!(f, "Can't watch files for changes: {err}"),
+        }
+    }
 }
 
 pub trait ToastyError<T> {
@@ -19,24 +62,8 @@ impl<T> ToastyError<T> for Result<T, HibikiError> {
     fn handle_toasty(self, toasts: &mut Toasts) -> Option<T> {
         match self {
             Ok(val) => Some(val),
-            Err(HibikiError::DoesNotExist(path)) => {
-                toasts.error(format!("File at '{path:?}' does not exist."));
-                None
-            }
-            Err(HibikiError::NotAFile(path)) => {
-                toasts.error(format!("'{path:?}' is not a file."));
-                None
-            }
-            Err(HibikiError::InternalError(err)) => {
-                toasts.error(format!("Internal error occured: {err:?}"));
-                None
-            }
-            Err(HibikiError::BrokenScene(err)) => {
-                toasts.error(format!("Broken scene file: {err:?}"));
-                None
-            }
-            Err(HibikiError::SceneSerialize(err)) => {
-                toasts.error(format!("Couldn't save scene: {err:?}"));
+            Err(err) => {
+                toasts.error(err.to_string());
                 None
             }
         }
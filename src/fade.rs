@@ -0,0 +1,117 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+const NOT_FADING: u64 = u64::MAX;
+
+/// Lets the UI fade out a playing voice, holds the requested length in microseconds
+#[derive(Clone)]
+pub struct FadeControl(Arc<AtomicU64>);
+
+impl Default for FadeControl {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(NOT_FADING)))
+    }
+}
+
+impl FadeControl {
+    pub fn fade_out(&self, duration: Duration) {
+        let micros = duration.as_micros().min(NOT_FADING as u128 - 1) as u64;
+        // keep the first request, so repeated stops don't restart the fade
+        let _ = self
+            .0
+            .compare_exchange(NOT_FADING, micros, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    fn requested(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Relaxed) {
+            NOT_FADING => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+}
+
+/// Fades the inner source in from silence when it starts,
+/// and out to silence once requested through its [`FadeControl`], ending it afterwards
+pub struct Fading<S> {
+    input: S,
+    control: FadeControl,
+    /// Samples played so far and the total length of the fade-in
+    fade_in: (u64, u64),
+    /// Samples left until silence and the total length of the fade
+    fade_out: Option<(u64, u64)>,
+}
+
+impl<S: Source<Item = f32>> Fading<S> {
+    pub fn new(input: S, control: FadeControl, fade_in: Duration) -> Self {
+        let samples_per_second = input.sample_rate() as f64 * input.channels() as f64;
+        let fade_in = (fade_in.as_secs_f64() * samples_per_second) as u64;
+        Self {
+            input,
+            control,
+            fade_in: (0, fade_in),
+            fade_out: None,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Fading<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.fade_out.is_none() {
+            if let Some(duration) = self.control.requested() {
+                let samples_per_second =
+                    self.input.sample_rate() as f64 * self.input.channels() as f64;
+                let total = (duration.as_secs_f64() * samples_per_second) as u64;
+                self.fade_out = Some((total, total));
+            }
+        }
+        let mut gain = 1.;
+        let (played, fade_in) = &mut self.fade_in;
+        if *played < *fade_in {
+            gain = *played as f32 / *fade_in as f32;
+            *played += 1;
+        }
+        match &mut self.fade_out {
+            None => {}
+            Some((0, _)) => return None,
+            Some((left, total)) => {
+                gain *= *left as f32 / *total as f32;
+                *left -= 1;
+            }
+        }
+        self.input.next().map(|sample| sample * gain)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Fading<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.fade_out {
+            Some((left, _)) => Some(
+                self.input
+                    .current_frame_len()
+                    .map_or(left as usize, |len| len.min(left as usize)),
+            ),
+            None => self.input.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
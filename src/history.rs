@@ -0,0 +1,210 @@
+use crate::sound::{Sound, SoundSettings};
+
+/// Number of edits that can be undone
+const MAX_HISTORY: usize = 100;
+
+/// A reversible change to the sounds of a board
+pub enum Edit {
+    Insert {
+        index: usize,
+        sounds: Vec<Sound>,
+    },
+    Remove {
+        index: usize,
+        count: usize,
+    },
+    Move {
+        from: usize,
+        to: usize,
+    },
+    Change {
+        index: usize,
+        settings: SoundSettings,
+    },
+    /// Several edits that get undone as a single step
+    Batch(Vec<Edit>),
+}
+
+impl Edit {
+    /// Applies the edit and returns the edit reverting it, `selected` follows the sound it points at
+    fn apply(self, sounds: &mut Vec<Sound>, selected: &mut Option<usize>) -> Edit {
+        match self {
+            Edit::Insert {
+                index,
+                sounds: inserted,
+            } => {
+                let count = inserted.len();
+                sounds.splice(index..index, inserted);
+                *selected = selected.map(|i| if i >= index { i + count } else { i });
+                Edit::Remove { index, count }
+            }
+            Edit::Remove { index, count } => {
+                let mut removed: Vec<Sound> = sounds.drain(index..index + count).collect();
+                *selected = selected.and_then(|i| match i {
+                    i if i < index => Some(i),
+                    i if i < index + count => None,
+                    i => Some(i - count),
+                });
+                // removed sounds would keep playing in the history otherwise
+                for sound in removed.iter_mut() {
+                    sound.stop(None);
+                    sound.voices.clear();
+                }
+                Edit::Insert {
+                    index,
+                    sounds: removed,
+                }
+            }
+            Edit::Move { from, to } => {
+                let sound = sounds.remove(from);
+                sounds.insert(to, sound);
+                *selected = selected.map(|i| match i {
+                    i if i == from => to,
+                    i if from < i && i <= to => i - 1,
+                    i if to <= i && i < from => i + 1,
+                    i => i,
+                });
+                Edit::Move { from: to, to: from }
+            }
+            Edit::Change { index, settings } => Edit::Change {
+                index,
+                settings: sounds[index].replace_settings(settings),
+            },
+            Edit::Batch(edits) => {
+                let mut reverts: Vec<Edit> = edits
+                    .into_iter()
+                    .map(|edit| edit.apply(sounds, selected))
+                    .collect();
+                reverts.reverse();
+                Edit::Batch(reverts)
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks, both hold the edits that revert the last step
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    /// Remembers how to revert a change that was just made
+    pub fn record(&mut self, revert: Edit) {
+        self.redo.clear();
+        self.undo.push(revert);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Returns whether there was anything to undo
+    pub fn undo(&mut self, sounds: &mut Vec<Sound>, selected: &mut Option<usize>) -> bool {
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(edit.apply(sounds, selected));
+        true
+    }
+
+    /// Returns whether there was anything to redo
+    pub fn redo(&mut self, sounds: &mut Vec<Sound>, selected: &mut Option<usize>) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(edit.apply(sounds, selected));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::sound::SoundSource;
+
+    fn sounds(names: &[&str]) -> Vec<Sound> {
+        names
+            .iter()
+            .map(|name| {
+                let mut sound = Sound::new(SoundSource::missing(PathBuf::from(name)));
+                sound.name = name.to_string();
+                sound
+            })
+            .collect()
+    }
+
+    fn names(sounds: &[Sound]) -> Vec<&str> {
+        sounds.iter().map(|sound| sound.name.as_str()).collect()
+    }
+
+    #[test]
+    fn move_round_trip_keeps_the_selection() {
+        let mut board = sounds(&["a", "b", "c", "d"]);
+        let mut history = History::default();
+        // "a" was dragged behind "c" and is still selected
+        Edit::Move { from: 0, to: 2 }.apply(&mut board, &mut None);
+        history.record(Edit::Move { from: 2, to: 0 });
+        let mut selected = Some(2);
+
+        assert!(history.undo(&mut board, &mut selected));
+        assert_eq!(names(&board), ["a", "b", "c", "d"]);
+        assert_eq!(selected, Some(0));
+
+        assert!(history.redo(&mut board, &mut selected));
+        assert_eq!(names(&board), ["b", "c", "a", "d"]);
+        assert_eq!(selected, Some(2));
+
+        // sounds in between shift by one
+        let mut other = Some(1);
+        assert!(history.undo(&mut board, &mut other));
+        assert_eq!(other, Some(2));
+    }
+
+    #[test]
+    fn batch_round_trip_keeps_the_selection() {
+        let mut board = sounds(&["a", "b", "c"]);
+        let mut history = History::default();
+        // "b" got duplicated and "a" removed in one step
+        let duplicate = sounds(&["b2"]);
+        let revert = Edit::Batch(vec![
+            Edit::Insert {
+                index: 2,
+                sounds: duplicate,
+            },
+            Edit::Remove { index: 0, count: 1 },
+        ])
+        .apply(&mut board, &mut None);
+        history.record(revert);
+        assert_eq!(names(&board), ["b", "b2", "c"]);
+        let mut selected = Some(2);
+
+        assert!(history.undo(&mut board, &mut selected));
+        assert_eq!(names(&board), ["a", "b", "c"]);
+        assert_eq!(selected, Some(2));
+
+        assert!(history.redo(&mut board, &mut selected));
+        assert_eq!(names(&board), ["b", "b2", "c"]);
+        assert_eq!(selected, Some(2));
+
+        // the duplicate goes away on undo, so does its selection
+        let mut selected = Some(1);
+        assert!(history.undo(&mut board, &mut selected));
+        assert_eq!(selected, None);
+    }
+}
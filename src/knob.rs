@@ -1,23 +1,81 @@
+use std::{f32::consts::PI, ops::RangeInclusive};
+
 use eframe::{
     egui::{CursorIcon, Response, Sense, Ui, Widget},
-    epaint::{Color32, Stroke, Vec2},
+    epaint::{Color32, Pos2, Shape, Stroke, Vec2},
 };
 
+/// Angle between the lowest value and straight up, the same on the other side for the highest value
+const SWEEP: f32 = PI * 0.75;
+
 pub struct Knob<'a> {
     pub hint_color: Color32,
     pub val: &'a mut f64,
+    pub range: RangeInclusive<f64>,
+    /// Value to reset to on double-click
+    pub default: f64,
+}
+
+impl Knob<'_> {
+    fn normalized(&self, value: f64) -> f32 {
+        let span = self.range.end() - self.range.start();
+        if span <= 0. {
+            return 0.;
+        }
+        ((value - self.range.start()) / span).clamp(0., 1.) as f32
+    }
 }
 
 impl Widget for Knob<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
-        let (rect, response) = ui.allocate_at_least(Vec2::splat(40.), Sense::drag());
-        ui.painter().circle_filled(
-            rect.center(),
-            rect.width() / 2.,
-            catppuccin_egui::MACCHIATO.surface1,
-        );
-        ui.painter().line_segment(
-            [rect.center(), rect.center_top()],
+        let (rect, mut response) = ui.allocate_at_least(Vec2::splat(40.), Sense::click_and_drag());
+
+        let span = self.range.end() - self.range.start();
+        let mut value = *self.val;
+        if response.dragged() {
+            // holding shift allows fine adjustments
+            let speed = if ui.input(|i| i.modifiers.shift) {
+                0.001
+            } else {
+                0.005
+            };
+            value -= response.drag_delta().y as f64 * speed * span;
+        }
+        if response.hovered() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            value += scroll as f64 * 0.001 * span;
+        }
+        if response.double_clicked() {
+            value = self.default;
+        }
+        let value = value.clamp(*self.range.start(), *self.range.end());
+        if value != *self.val {
+            *self.val = value;
+            response.mark_changed();
+        }
+
+        let radius = rect.width() / 2.;
+        let point_at = |t: f32, radius: f32| {
+            let angle = -SWEEP + t * 2. * SWEEP;
+            rect.center() + radius * Vec2::new(angle.sin(), -angle.cos())
+        };
+        let painter = ui.painter();
+        painter.circle_filled(rect.center(), radius, catppuccin_egui::MACCHIATO.surface1);
+
+        // the arc starts at zero if possible, so bipolar values like pan grow from the center
+        let origin = self.normalized(0.);
+        let current = self.normalized(value);
+        let (from, to) = if origin < current {
+            (origin, current)
+        } else {
+            (current, origin)
+        };
+        let points: Vec<Pos2> = (0..=32)
+            .map(|i| point_at(from + (to - from) * i as f32 / 32., radius - 2.))
+            .collect();
+        painter.add(Shape::line(points, Stroke::new(3., self.hint_color)));
+        painter.line_segment(
+            [rect.center(), point_at(current, radius - 2.)],
             Stroke::new(4., self.hint_color),
         );
 
@@ -25,6 +83,6 @@ impl Widget for Knob<'_> {
             ui.ctx().set_cursor_icon(CursorIcon::ResizeVertical);
         }
 
-        response
+        response.on_hover_text(format!("{value:.2}"))
     }
 }
@@ -0,0 +1,193 @@
+use std::fmt::Display;
+
+use eframe::epaint::{Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Side length of a single grid cell
+pub const CELL_SIZE: f32 = 50.;
+/// Space between two grid cells
+pub const CELL_SPACING: f32 = 8.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum PadSize {
+    #[default]
+    Single,
+    Wide,
+    Large,
+}
+
+impl PadSize {
+    /// Columns and rows covered by a pad of this size
+    pub fn cells(self) -> (usize, usize) {
+        match self {
+            PadSize::Single => (1, 1),
+            PadSize::Wide => (2, 1),
+            PadSize::Large => (2, 2),
+        }
+    }
+}
+
+impl Display for PadSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PadSize::Single => f.write_str("1x1"),
+            PadSize::Wide => f.write_str("2x1"),
+            PadSize::Large => f.write_str("2x2"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Layout {
+    pub columns: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { columns: 8 }
+    }
+}
+
+/// Cell area of a placed pad
+#[derive(Clone, Copy)]
+pub struct Placement {
+    pub column: usize,
+    pub row: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Placement {
+    pub fn rect(&self, origin: Pos2) -> Rect {
+        let step = CELL_SIZE + CELL_SPACING;
+        Rect::from_min_size(
+            origin + Vec2::new(self.column as f32, self.row as f32) * step,
+            Vec2::new(self.columns as f32, self.rows as f32) * step - Vec2::splat(CELL_SPACING),
+        )
+    }
+}
+
+impl Layout {
+    /// Places the pads in order into the first free cells after skipping their gap of empty slots
+    pub fn arrange(&self, pads: impl Iterator<Item = (PadSize, usize)>) -> Vec<Placement> {
+        let columns = self.columns.max(1);
+        let mut occupied: Vec<bool> = vec![];
+        let is_free = |occupied: &Vec<bool>, column: usize, row: usize| {
+            !occupied
+                .get(row * columns + column)
+                .copied()
+                .unwrap_or(false)
+        };
+        let mut cursor = 0;
+        pads.map(|(size, gap)| {
+            let (width, height) = size.cells();
+            let width = width.min(columns);
+            let mut cell = cursor + gap;
+            let (column, row) = loop {
+                let (column, row) = (cell % columns, cell / columns);
+                let fits = column + width <= columns
+                    && (0..height)
+                        .all(|y| (0..width).all(|x| is_free(&occupied, column + x, row + y)));
+                if fits {
+                    break (column, row);
+                }
+                cell += 1;
+            };
+            for y in 0..height {
+                for x in 0..width {
+                    let index = (row + y) * columns + column + x;
+                    if occupied.len() <= index {
+                        occupied.resize(index + 1, false);
+                    }
+                    occupied[index] = true;
+                }
+            }
+            cursor = cell + width;
+            Placement {
+                column,
+                row,
+                columns: width,
+                rows: height,
+            }
+        })
+        .collect()
+    }
+
+    /// Rows needed to show all placements
+    pub fn rows(&self, placements: &[Placement]) -> usize {
+        placements
+            .iter()
+            .map(|placement| placement.row + placement.rows)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Size of the area needed to show all placements
+    pub fn size(&self, placements: &[Placement]) -> Vec2 {
+        let step = CELL_SIZE + CELL_SPACING;
+        Vec2::new(self.columns.max(1) as f32, self.rows(placements) as f32) * step
+            - Vec2::splat(CELL_SPACING)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(columns: usize, pads: &[(PadSize, usize)]) -> Vec<(usize, usize, usize, usize)> {
+        Layout { columns }
+            .arrange(pads.iter().copied())
+            .iter()
+            .map(|placement| {
+                (
+                    placement.column,
+                    placement.row,
+                    placement.columns,
+                    placement.rows,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_pads_in_narrow_grids() {
+        let pads = [
+            (PadSize::Large, 0),
+            (PadSize::Large, 0),
+            (PadSize::Single, 0),
+        ];
+        assert_eq!(cells(2, &pads), [(0, 0, 2, 2), (0, 2, 2, 2), (0, 4, 1, 1)]);
+        assert_eq!(cells(1, &pads), [(0, 0, 1, 2), (0, 2, 1, 2), (0, 4, 1, 1)]);
+    }
+
+    #[test]
+    fn later_single_pads_fill_gaps() {
+        // the single pad after the large one goes below the first single pad
+        let pads = [
+            (PadSize::Single, 0),
+            (PadSize::Large, 0),
+            (PadSize::Single, 0),
+        ];
+        assert_eq!(cells(3, &pads), [(0, 0, 1, 1), (1, 0, 2, 2), (0, 1, 1, 1)]);
+        // the wide pad wraps as it doesn't fit after its empty slot, the single pad goes next to it
+        let pads = [
+            (PadSize::Single, 0),
+            (PadSize::Wide, 1),
+            (PadSize::Single, 0),
+        ];
+        assert_eq!(cells(3, &pads), [(0, 0, 1, 1), (0, 1, 2, 1), (2, 1, 1, 1)]);
+        // empty slots in front of a pad
+        let pads = [(PadSize::Single, 0), (PadSize::Single, 2)];
+        assert_eq!(cells(4, &pads), [(0, 0, 1, 1), (3, 0, 1, 1)]);
+    }
+
+    #[test]
+    fn pads_wider_than_the_grid_get_clamped() {
+        let pads = [(PadSize::Wide, 0), (PadSize::Large, 0)];
+        assert_eq!(cells(1, &pads), [(0, 0, 1, 1), (0, 1, 1, 2)]);
+        // no columns at all is treated as a single one
+        assert_eq!(cells(0, &pads), [(0, 0, 1, 1), (0, 1, 1, 2)]);
+        let layout = Layout { columns: 1 };
+        assert_eq!(layout.rows(&layout.arrange(pads.into_iter())), 3);
+    }
+}
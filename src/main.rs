@@ -1,38 +1,74 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
+use audio::{Outputs, Route};
 use board::Board;
+use clap::Parser;
+use cli::Cli;
+use duck::DuckRole;
 use eframe::egui::{self, color_picker::Alpha, Label, RichText, Widget};
 use egui_notify::Toasts;
+use error::ToastyError;
 use knob::Knob;
-use rodio::OutputStream;
+use layout::PadSize;
+use midi::{Midi, MidiTarget};
+use settings::Settings;
 use sound::SoundKind;
+use watcher::Watcher;
+use waveform::Waveform;
 
+mod audio;
 mod board;
+mod bundle;
+mod cli;
+mod duck;
 mod error;
+mod fade;
+mod history;
 mod knob;
+mod layout;
+mod midi;
+mod pan;
+mod progress;
 mod scene;
+mod settings;
 mod sound;
 mod trigger;
+mod trim;
+mod watcher;
+mod waveform;
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> ExitCode {
     env_logger::init();
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        return command.run();
+    }
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title("Hibiki")
             .with_inner_size([1280., 720.]),
         ..Default::default()
     };
-    eframe::run_native("Hibiki", options, Box::new(|cc| Box::new(Hibiki::new(cc))))
+    let result = eframe::run_native(
+        "Hibiki",
+        options,
+        Box::new(|cc| Box::new(Hibiki::new(cc, cli.scene))),
+    );
+    if let Err(err) = result {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
 
 struct Hibiki {
     toasts: Toasts,
     board: Board,
-    _stream: OutputStream,
 }
 
 impl Hibiki {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// Opens `scene_path` or reopens the last scene
+    pub fn new(cc: &eframe::CreationContext<'_>, scene_path: Option<PathBuf>) -> Self {
         catppuccin_egui::set_theme(&cc.egui_ctx, catppuccin_egui::MACCHIATO);
         let mut fonts = egui::FontDefinitions::default();
         fonts.font_data.insert(
@@ -46,25 +82,61 @@ impl Hibiki {
             .push("DelaGothicOne".to_owned());
         cc.egui_ctx.set_fonts(fonts);
 
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        let settings: Settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
 
         let mut toasts = Toasts::default();
 
+        let mut midi = Midi::new(cc.egui_ctx.clone());
+        midi.open_virtual_port().handle_toasty(&mut toasts);
+
+        let outputs = Outputs::new(
+            settings.output_device,
+            settings.monitor_device,
+            settings.monitor_volume,
+            &mut toasts,
+        );
+
+        let scene_path = scene_path
+            .or_else(|| {
+                settings
+                    .recent_scenes
+                    .first()
+                    .filter(|path| path.is_file())
+                    .cloned()
+            })
+            .unwrap_or_else(|| PathBuf::from("scene.hibiki.ron"));
+        let watcher = Watcher::new(cc.egui_ctx.clone()).handle_toasty(&mut toasts);
+
         let board = Board::new(
-            PathBuf::from("scene.hibiki.ron"),
-            stream_handle,
+            scene_path,
+            settings.recent_scenes,
+            settings.autosave,
+            outputs,
+            midi,
+            watcher,
             &mut toasts,
         );
 
-        Self {
-            toasts,
-            board,
-            _stream,
-        }
+        Self { toasts, board }
     }
 }
 
 impl eframe::App for Hibiki {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let outputs = self.board.outputs();
+        let settings = Settings {
+            output_device: outputs.main_device().map(str::to_owned),
+            monitor_device: outputs.monitor_device().map(str::to_owned),
+            monitor_volume: outputs.monitor_volume,
+            recent_scenes: self.board.recent_scenes().to_vec(),
+            autosave: self.board.autosave(),
+        };
+        eframe::set_value(storage, eframe::APP_KEY, &settings);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.centered_and_justified(|ui| {
@@ -80,7 +152,29 @@ impl eframe::App for Hibiki {
             });
             self.board.ui(ui, &mut self.toasts);
             egui::Window::new("Controller").show(ctx, |ui| {
+                let learning_hotkey = self.board.is_learning_hotkey();
+                let mut learn_hotkey = false;
+                let midi_learning = self.board.midi_learning();
+                let mut learn_midi = None;
+                let mut remove = false;
+                let mut duplicate = false;
+                let mut stop = false;
                 if let Some(controller) = self.board.selected_controller_mut() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Stop")
+                            .on_hover_text("Middle-click on the pad")
+                            .clicked()
+                        {
+                            stop = true;
+                        }
+                        if ui.button("Duplicate").clicked() {
+                            duplicate = true;
+                        }
+                        if ui.button("Delete").clicked() {
+                            remove = true;
+                        }
+                    });
                     ui.label(
                         controller
                             .source
@@ -89,7 +183,16 @@ impl eframe::App for Hibiki {
                             .unwrap()
                             .to_str()
                             .unwrap(),
-                    );
+                    )
+                    .on_hover_text(controller.source.path.display().to_string());
+                    ui.horizontal(|ui| {
+                        ui.label("Name: ");
+                        ui.text_edit_singleline(&mut controller.name);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Icon: ");
+                        ui.add(egui::TextEdit::singleline(&mut controller.icon).desired_width(40.));
+                    });
                     egui::widgets::color_picker::color_edit_button_srgba(
                         ui,
                         &mut controller.color,
@@ -132,6 +235,204 @@ impl eframe::App for Hibiki {
                                 );
                             });
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Size: ");
+                        egui::ComboBox::from_id_source("PadSize")
+                            .selected_text(format!("{}", controller.size))
+                            .show_ui(ui, |ui| {
+                                for size in [PadSize::Single, PadSize::Wide, PadSize::Large] {
+                                    ui.selectable_value(
+                                        &mut controller.size,
+                                        size,
+                                        format!("{size}"),
+                                    );
+                                }
+                            });
+                        ui.label("Empty slots before: ");
+                        ui.add(egui::DragValue::new(&mut controller.gap).clamp_range(0..=64));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Output: ");
+                        egui::ComboBox::from_id_source("Route")
+                            .selected_text(format!("{}", controller.route))
+                            .show_ui(ui, |ui| {
+                                for route in [Route::Main, Route::Monitor, Route::Both] {
+                                    ui.selectable_value(
+                                        &mut controller.route,
+                                        route,
+                                        format!("{route}"),
+                                    );
+                                }
+                            });
+                    });
+                    // unknown lengths leave the trim points unbounded
+                    let length = controller
+                        .source
+                        .duration()
+                        .map_or(f64::INFINITY, |duration| duration.as_secs_f64());
+                    ui.horizontal(|ui| {
+                        let trim = &mut controller.trim;
+                        ui.label("Start: ");
+                        ui.add(
+                            egui::DragValue::new(&mut trim.start)
+                                .clamp_range(0.0..=length)
+                                .speed(0.01)
+                                .suffix(" s"),
+                        );
+                        let mut has_end = trim.end.is_some();
+                        if ui.checkbox(&mut has_end, "End: ").changed() {
+                            trim.end = has_end.then_some(length.min(trim.start + 1.));
+                        }
+                        let start = trim.start;
+                        if let Some(end) = &mut trim.end {
+                            ui.add(
+                                egui::DragValue::new(end)
+                                    .clamp_range(start..=length)
+                                    .speed(0.01)
+                                    .suffix(" s"),
+                            );
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let trim = &mut controller.trim;
+                        let mut has_loop = trim.loop_region.is_some();
+                        if ui
+                            .checkbox(&mut has_loop, "Loop from ")
+                            .on_hover_text("Region repeated by the repeating kinds")
+                            .changed()
+                        {
+                            let end = trim.end.unwrap_or(length);
+                            trim.loop_region =
+                                has_loop.then_some((trim.start, end.min(trim.start + 1.)));
+                        }
+                        let (start, end) = (trim.start, trim.end.unwrap_or(length));
+                        if let Some((loop_in, loop_out)) = &mut trim.loop_region {
+                            ui.add(
+                                egui::DragValue::new(loop_in)
+                                    .clamp_range(start..=end)
+                                    .speed(0.01)
+                                    .suffix(" s"),
+                            );
+                            ui.label("to");
+                            ui.add(
+                                egui::DragValue::new(loop_out)
+                                    .clamp_range(start..=end)
+                                    .speed(0.01)
+                                    .suffix(" s"),
+                            );
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Fade in: ");
+                        ui.add(
+                            egui::DragValue::new(&mut controller.fade_in)
+                                .clamp_range(0.0..=60.0)
+                                .speed(0.01)
+                                .suffix(" s"),
+                        );
+                        ui.label("Fade out: ")
+                            .on_hover_text("Used whenever the sound gets stopped");
+                        ui.add(
+                            egui::DragValue::new(&mut controller.fade_out)
+                                .clamp_range(0.0..=60.0)
+                                .speed(0.01)
+                                .suffix(" s"),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Choke group: ").on_hover_text(
+                            "Starting this sound stops the other sounds of the same group",
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut controller.choke_group)
+                                .hint_text("None")
+                                .desired_width(80.),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        let ducking = &mut controller.ducking;
+                        ui.label("Ducking: ");
+                        egui::ComboBox::from_id_source("DuckRole")
+                            .selected_text(format!("{}", ducking.role))
+                            .show_ui(ui, |ui| {
+                                for role in [DuckRole::None, DuckRole::Ducks, DuckRole::Ducked] {
+                                    ui.selectable_value(&mut ducking.role, role, format!("{role}"));
+                                }
+                            });
+                    });
+                    if controller.ducking.role == DuckRole::Ducked {
+                        ui.horizontal(|ui| {
+                            let ducking = &mut controller.ducking;
+                            ui.label("Depth: ");
+                            ui.add(
+                                egui::DragValue::new(&mut ducking.depth)
+                                    .clamp_range(0.0..=1.0)
+                                    .speed(0.01),
+                            )
+                            .on_hover_text("Part of the volume taken away, 1 silences the sound");
+                            ui.label("Attack: ");
+                            ui.add(
+                                egui::DragValue::new(&mut ducking.attack)
+                                    .clamp_range(0.0..=10.0)
+                                    .speed(0.01)
+                                    .suffix(" s"),
+                            );
+                            ui.label("Release: ");
+                            ui.add(
+                                egui::DragValue::new(&mut ducking.release)
+                                    .clamp_range(0.0..=10.0)
+                                    .speed(0.01)
+                                    .suffix(" s"),
+                            );
+                        });
+                    }
+                    let playhead = controller.playhead();
+                    ui.add(Waveform {
+                        peaks: controller
+                            .source
+                            .peaks
+                            .get(&controller.source.data, ui.ctx()),
+                        trim: &mut controller.trim,
+                        playhead,
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Hotkey: ");
+                        if learning_hotkey {
+                            ui.label(RichText::new("Press a key...").italics());
+                        } else {
+                            ui.label(controller.hotkey.map_or("None", |key| key.name()));
+                            if ui.button("Learn").clicked() {
+                                learn_hotkey = true;
+                            }
+                            if controller.hotkey.is_some() && ui.button("Clear").clicked() {
+                                controller.hotkey = None;
+                            }
+                        }
+                    });
+                    for (target, label, mapped) in [
+                        (MidiTarget::Note, "MIDI Note: ", &mut controller.midi.note),
+                        (
+                            MidiTarget::VolumeCc,
+                            "Volume CC: ",
+                            &mut controller.midi.volume_cc,
+                        ),
+                        (MidiTarget::PanCc, "Pan CC: ", &mut controller.midi.pan_cc),
+                    ] {
+                        ui.horizontal(|ui| {
+                            ui.label(label);
+                            if midi_learning == Some(target) {
+                                ui.label(RichText::new("Waiting for MIDI...").italics());
+                            } else {
+                                ui.label(mapped.map_or("None".to_owned(), |v| v.to_string()));
+                                if ui.button("Learn").clicked() {
+                                    learn_midi = Some(target);
+                                }
+                                if mapped.is_some() && ui.button("Clear").clicked() {
+                                    *mapped = None;
+                                }
+                            }
+                        });
+                    }
                     ui.add_space(5.);
                     ui.horizontal(|ui| {
                         ui.vertical(|ui| {
@@ -139,21 +440,14 @@ impl eframe::App for Hibiki {
                             let volume = Knob {
                                 hint_color: catppuccin_egui::MACCHIATO.yellow,
                                 val: &mut controller.volume,
+                                range: 0.0..=10.0,
+                                default: 1.0,
                             };
                             volume.ui(ui);
-
-                            let vol_ref = &mut controller.volume;
-                            let sink_ref = &mut controller.sink;
                             ui.add(
-                                egui::DragValue::from_get_set(move |v: Option<f64>| {
-                                    if let Some(v) = v {
-                                        *vol_ref = v;
-                                        sink_ref.set_volume(v as f32);
-                                    }
-                                    *vol_ref
-                                })
-                                .clamp_range(0..=10)
-                                .speed(0.02),
+                                egui::DragValue::new(&mut controller.volume)
+                                    .clamp_range(0..=10)
+                                    .speed(0.02),
                             );
                         });
 
@@ -162,6 +456,8 @@ impl eframe::App for Hibiki {
                             let pan = Knob {
                                 hint_color: catppuccin_egui::MACCHIATO.blue,
                                 val: &mut controller.pan,
+                                range: -1.0..=1.0,
+                                default: 0.0,
                             };
                             pan.ui(ui);
                             ui.add(
@@ -174,6 +470,21 @@ impl eframe::App for Hibiki {
                 } else {
                     ui.label(RichText::new("Right-click on a sound to inspect").italics());
                 }
+                if learn_hotkey {
+                    self.board.learn_hotkey();
+                }
+                if let Some(target) = learn_midi {
+                    self.board.learn_midi(target);
+                }
+                if stop {
+                    self.board.stop_selected_sound();
+                }
+                if duplicate {
+                    self.board.duplicate_selected_sound();
+                }
+                if remove {
+                    self.board.remove_selected_sound();
+                }
             });
             self.toasts.show(ui.ctx());
         });
@@ -0,0 +1,210 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryIter};
+
+use eframe::egui;
+use midir::{Ignore, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::HibikiError;
+
+const CLIENT_NAME: &str = "Hibiki";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MidiMessage {
+    NoteOn { note: u8 },
+    NoteOff { note: u8 },
+    ControlChange { controller: u8, value: u8 },
+}
+
+impl MidiMessage {
+    /// Parses the channel messages we care about, the channel itself is ignored
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            // a note-on with zero velocity is a note-off by convention
+            [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+                Some(Self::NoteOn { note })
+            }
+            [status, note, _] if status & 0xF0 == 0x90 || status & 0xF0 == 0x80 => {
+                Some(Self::NoteOff { note })
+            }
+            [status, controller, value] if status & 0xF0 == 0xB0 => {
+                Some(Self::ControlChange { controller, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// MIDI mapping of a single sound
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MidiMapping {
+    pub note: Option<u8>,
+    pub volume_cc: Option<u8>,
+    pub pan_cc: Option<u8>,
+}
+
+/// Part of a `MidiMapping` that waits for the next matching message
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MidiTarget {
+    Note,
+    VolumeCc,
+    PanCc,
+}
+
+/// Receives messages from a connected MIDI port and from our own virtual port
+pub struct Midi {
+    ctx: egui::Context,
+    sender: Sender<MidiMessage>,
+    receiver: Receiver<MidiMessage>,
+    connection: Option<(String, MidiInputConnection<()>)>,
+    _virtual_port: Option<MidiInputConnection<()>>,
+}
+
+impl Midi {
+    pub fn new(ctx: egui::Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            ctx,
+            sender,
+            receiver,
+            connection: None,
+            _virtual_port: None,
+        }
+    }
+
+    pub fn messages(&self) -> TryIter<'_, MidiMessage> {
+        self.receiver.try_iter()
+    }
+
+    /// Feeds raw bytes through the same path as a port would
+    #[cfg(test)]
+    pub fn inject(&self, bytes: &[u8]) {
+        if let Some(message) = MidiMessage::parse(bytes) {
+            let _ = self.sender.send(message);
+        }
+    }
+
+    fn input() -> Result<midir::MidiInput, HibikiError> {
+        let mut input = midir::MidiInput::new(CLIENT_NAME)
+            .map_err(|err| HibikiError::MidiError(err.to_string()))?;
+        input.ignore(Ignore::All);
+        Ok(input)
+    }
+
+    fn callback(&self) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
+        let sender = self.sender.clone();
+        let ctx = self.ctx.clone();
+        move |_, bytes, _| {
+            if let Some(message) = MidiMessage::parse(bytes) {
+                let _ = sender.send(message);
+                ctx.request_repaint();
+            }
+        }
+    }
+
+    /// Names of all available input ports
+    pub fn ports() -> Result<Vec<String>, HibikiError> {
+        let input = Self::input()?;
+        Ok(input
+            .ports()
+            .iter()
+            .filter_map(|port| input.port_name(port).ok())
+            .collect())
+    }
+
+    pub fn connected_port(&self) -> Option<&str> {
+        self.connection.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn connect(&mut self, port_name: &str) -> Result<(), HibikiError> {
+        self.disconnect();
+        let input = Self::input()?;
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).is_ok_and(|name| name == port_name))
+            .ok_or_else(|| HibikiError::MidiError(format!("Port '{port_name}' is gone")))?;
+        let connection = input
+            .connect(&port, "hibiki-in", self.callback(), ())
+            .map_err(|err| HibikiError::MidiError(err.to_string()))?;
+        self.connection = Some((port_name.to_owned(), connection));
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        if let Some((_, connection)) = self.connection.take() {
+            connection.close();
+        }
+    }
+
+    /// Opens a virtual port other applications can connect to, useful without hardware
+    #[cfg(unix)]
+    pub fn open_virtual_port(&mut self) -> Result<(), HibikiError> {
+        use midir::os::unix::VirtualInput;
+
+        let connection = Self::input()?
+            .create_virtual(CLIENT_NAME, self.callback(), ())
+            .map_err(|err| HibikiError::MidiError(err.to_string()))?;
+        self._virtual_port = Some(connection);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn open_virtual_port(&mut self) -> Result<(), HibikiError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(bytes: &[u8]) -> Vec<MidiMessage> {
+        let midi = Midi::new(egui::Context::default());
+        midi.inject(bytes);
+        midi.messages().collect()
+    }
+
+    #[test]
+    fn note_on() {
+        assert_eq!(
+            received(&[0x90, 60, 100]),
+            [MidiMessage::NoteOn { note: 60 }]
+        );
+        // the channel is ignored
+        assert_eq!(received(&[0x9F, 61, 1]), [MidiMessage::NoteOn { note: 61 }]);
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_note_off() {
+        assert_eq!(
+            received(&[0x90, 60, 0]),
+            [MidiMessage::NoteOff { note: 60 }]
+        );
+    }
+
+    #[test]
+    fn note_off() {
+        assert_eq!(
+            received(&[0x83, 60, 64]),
+            [MidiMessage::NoteOff { note: 60 }]
+        );
+    }
+
+    #[test]
+    fn control_change() {
+        assert_eq!(
+            received(&[0xB0, 7, 127]),
+            [MidiMessage::ControlChange {
+                controller: 7,
+                value: 127
+            }]
+        );
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        assert!(received(&[0xC0, 5]).is_empty());
+        assert!(received(&[0xF8]).is_empty());
+        assert!(received(&[0xE0, 0, 64]).is_empty());
+    }
+}
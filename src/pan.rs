@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+/// Pan position shared with all playing sources of a sound, so it can be changed live
+#[derive(Clone, Default)]
+pub struct PanControl(Arc<AtomicU32>);
+
+impl PanControl {
+    pub fn new(pan: f64) -> Self {
+        let control = Self::default();
+        control.set(pan);
+        control
+    }
+
+    pub fn set(&self, pan: f64) {
+        let pan = pan.clamp(-1., 1.) as f32;
+        self.0.store(pan.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Gains of the left and right channel. The centered position keeps both channels untouched.
+    fn gains(&self) -> (f32, f32) {
+        let pan = self.get();
+        ((1. - pan).min(1.), (1. + pan).min(1.))
+    }
+}
+
+/// Stereo panning stage. Mono sources get upmixed to stereo, stereo sources get balanced.
+/// Sources with more channels pass through untouched.
+pub struct Panned<S> {
+    input: S,
+    control: PanControl,
+    input_channels: u16,
+    /// Right sample of an upmixed mono sample that still needs to be emitted
+    pending: Option<f32>,
+    /// Channel of the next stereo sample
+    channel: u16,
+}
+
+impl<S: Source<Item = f32>> Panned<S> {
+    pub fn new(input: S, control: PanControl) -> Self {
+        Self {
+            input_channels: input.channels(),
+            input,
+            control,
+            pending: None,
+            channel: 0,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Panned<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending.take() {
+            return Some(right);
+        }
+        if self.channel == 0 {
+            // channel count can only change between frames
+            self.input_channels = self.input.channels();
+        }
+        let sample = self.input.next()?;
+        let (left, right) = self.control.gains();
+        match self.input_channels {
+            1 => {
+                self.pending = Some(sample * right);
+                Some(sample * left)
+            }
+            2 => {
+                let gain = if self.channel == 0 { left } else { right };
+                self.channel = (self.channel + 1) % 2;
+                Some(sample * gain)
+            }
+            _ => Some(sample),
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Panned<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.input_channels {
+            1 => self
+                .input
+                .current_frame_len()
+                .map(|len| len * 2 + usize::from(self.pending.is_some())),
+            _ => self.input.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self.input_channels {
+            1 => 2,
+            channels => channels,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+#[derive(Default)]
+struct ProgressState {
+    samples: AtomicU64,
+    samples_per_second: AtomicU32,
+}
+
+/// Playback position of a single voice, shared with the audio thread
+#[derive(Clone, Default)]
+pub struct Progress(Arc<ProgressState>);
+
+impl Progress {
+    pub fn elapsed(&self) -> Duration {
+        let samples_per_second = self.0.samples_per_second.load(Ordering::Relaxed);
+        if samples_per_second == 0 {
+            return Duration::ZERO;
+        }
+        let samples = self.0.samples.load(Ordering::Relaxed);
+        Duration::from_secs_f64(samples as f64 / samples_per_second as f64)
+    }
+}
+
+/// Reports how far the inner source got into a [`Progress`]
+pub struct Tracked<S> {
+    input: S,
+    progress: Progress,
+}
+
+impl<S: Source<Item = f32>> Tracked<S> {
+    pub fn new(input: S, progress: Progress) -> Self {
+        Self { input, progress }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Tracked<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let state = &self.progress.0;
+        state.samples_per_second.store(
+            self.input.sample_rate() * self.input.channels() as u32,
+            Ordering::Relaxed,
+        );
+        state.samples.fetch_add(1, Ordering::Relaxed);
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Tracked<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -1,17 +1,56 @@
 use std::{
-    fs::{File, OpenOptions},
-    path::PathBuf,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Component, Path, PathBuf},
 };
 
 use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 
-use crate::{error::HibikiError, sound::SoundKind};
+use crate::{
+    audio::Route,
+    duck::Ducking,
+    error::HibikiError,
+    layout::{Layout, PadSize},
+    midi::MidiMapping,
+    sound::SoundKind,
+    trim::Trim,
+};
+
+/// Format version written by this build, bump it and extend [`Scene::migrate`] on breaking changes
+pub const SCENE_VERSION: u32 = 1;
 
+#[derive(PartialEq, Deserialize, Serialize)]
 pub struct Scene {
+    /// Format version the file was written with, files without one predate versioning
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default = "default_master_volume")]
+    pub master_volume: f64,
     pub entries: Box<[SceneEntry]>,
 }
 
+fn default_master_volume() -> f64 {
+    1.0
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            name: String::new(),
+            layout: Layout::default(),
+            master_volume: default_master_volume(),
+            entries: Box::new([]),
+        }
+    }
+}
+
 impl Scene {
     pub fn load(scene_path: &PathBuf) -> Result<Self, HibikiError> {
         if scene_path.exists() {
@@ -19,15 +58,40 @@ impl Scene {
             if !scene_path.is_file() {
                 return Err(HibikiError::NotAFile(scene_path.clone()));
             }
-            let file = File::open(&scene_path).map_err(HibikiError::InternalError)?;
-            Ok(Self {
-                entries: ron::de::from_reader(&file).map_err(HibikiError::BrokenScene)?,
-            })
+            let text = fs::read_to_string(scene_path).map_err(HibikiError::InternalError)?;
+            let mut scene: Self = ron::from_str(&text)
+                .or_else(|err| {
+                    // scenes used to be a bare list of entries
+                    ron::from_str(&text)
+                        .map(|entries| Self {
+                            version: 0,
+                            entries,
+                            ..Default::default()
+                        })
+                        .map_err(|_| err)
+                })
+                .map_err(HibikiError::BrokenScene)?
+                .migrate()?;
+            let dir = scene_dir(scene_path);
+            for entry in scene.entries.iter_mut() {
+                entry.sound_path = normalize(&dir.join(&entry.sound_path));
+            }
+            Ok(scene)
         } else {
-            Ok(Self {
-                entries: Box::new([]),
-            })
+            Ok(Self::default())
+        }
+    }
+
+    /// Upgrades a scene read from an older format, it gets written in the current one on save
+    fn migrate(mut self) -> Result<Self, HibikiError> {
+        if self.version > SCENE_VERSION {
+            return Err(HibikiError::UnsupportedSceneVersion(self.version));
+        }
+        if self.version == 0 {
+            // bare lists and unversioned scenes only lack fields that have defaults
+            self.version = 1;
         }
+        Ok(self)
     }
 
     pub fn save(&self, scene_path: &PathBuf) -> Result<(), HibikiError> {
@@ -35,18 +99,313 @@ impl Scene {
             .write(true)
             .create(true)
             .truncate(true)
-            .open(&scene_path)
+            .open(scene_path)
             .map_err(HibikiError::InternalError)?;
-        ron::ser::to_writer_pretty(file, &self.entries, PrettyConfig::default())
+        self.write(file)
+    }
+
+    pub fn write(&self, writer: impl Write) -> Result<(), HibikiError> {
+        ron::ser::to_writer_pretty(writer, self, PrettyConfig::default())
             .map_err(HibikiError::SceneSerialize)
     }
 }
 
-#[derive(Deserialize, Serialize)]
+/// Sidecar file unsaved changes of the scene get written to periodically
+pub fn autosave_path(scene_path: &Path) -> PathBuf {
+    let mut file_name = scene_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".autosave");
+    scene_path.with_file_name(file_name)
+}
+
+/// Directory the sound paths of a scene are relative to
+fn scene_dir(scene_path: &Path) -> PathBuf {
+    let dir = scene_path.parent().unwrap_or(Path::new(""));
+    if dir.is_absolute() {
+        dir.to_owned()
+    } else {
+        env::current_dir()
+            .map(|cwd| cwd.join(dir))
+            .unwrap_or_else(|_| dir.to_owned())
+    }
+}
+
+/// Resolves `.` and `..` without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Expresses `path` relative to the directory of the scene with `/` separators,
+/// so scenes can be moved along with their sounds and shared between systems.
+/// Paths that only share the root with the scene stay absolute.
+pub fn relative_sound_path(path: &Path, scene_path: &Path) -> PathBuf {
+    let dir = scene_dir(scene_path);
+    let mut path_components = path.components().peekable();
+    let mut dir_components = dir.components().peekable();
+    let mut shared_dirs = 0;
+    while path_components.peek().is_some() && path_components.peek() == dir_components.peek() {
+        if let Some(Component::Normal(_)) = path_components.next() {
+            shared_dirs += 1;
+        }
+        dir_components.next();
+    }
+    if shared_dirs == 0 {
+        return path.to_owned();
+    }
+    let parts: Vec<String> = dir_components
+        .map(|_| "..".to_owned())
+        .chain(
+            path_components.map(|component| component.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect();
+    PathBuf::from(parts.join("/"))
+}
+
+#[derive(PartialEq, Deserialize, Serialize)]
 pub struct SceneEntry {
+    /// Relative to the scene file when saved, absolute once loaded
     pub sound_path: PathBuf,
     pub controller: SoundKind,
     pub volume: f64,
     pub pan: f64,
     pub color: [u8; 3],
+    /// Name shown on the pad, the file stem is used if missing
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub icon: String,
+    /// Name of the `egui::Key` that triggers this sound
+    #[serde(default)]
+    pub hotkey: Option<String>,
+    #[serde(default)]
+    pub midi: MidiMapping,
+    #[serde(default)]
+    pub size: PadSize,
+    /// Empty grid slots in front of this sound
+    #[serde(default)]
+    pub gap: usize,
+    #[serde(default)]
+    pub route: Route,
+    #[serde(default)]
+    pub trim: Trim,
+    /// Fade-in and fade-out in seconds
+    #[serde(default)]
+    pub fade_in: f64,
+    #[serde(default)]
+    pub fade_out: f64,
+    /// Starting this sound stops the others in the same group, empty for none
+    #[serde(default)]
+    pub choke_group: String,
+    #[serde(default)]
+    pub ducking: Ducking,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bundle, sound::SoundSource};
+
+    /// Empty directory in the system's temp dir that is unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hibiki-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn normalize_resolves_dots() {
+        assert_eq!(normalize(Path::new("/a/b/../c/./d")), Path::new("/a/c/d"));
+        assert_eq!(normalize(Path::new("a/../../b")), Path::new("../b"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sound_paths_are_relative_to_the_scene() {
+        let scene_path = Path::new("/srv/show/scene.hibiki.ron");
+        assert_eq!(
+            relative_sound_path(Path::new("/srv/show/sounds/a.wav"), scene_path),
+            Path::new("sounds/a.wav")
+        );
+        assert_eq!(
+            relative_sound_path(Path::new("/srv/jingles/b.wav"), scene_path),
+            Path::new("../jingles/b.wav")
+        );
+        assert_eq!(
+            relative_sound_path(Path::new("/srv/c.wav"), scene_path),
+            Path::new("../c.wav")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_sharing_only_the_root_stay_absolute() {
+        let scene_path = Path::new("/srv/show/scene.hibiki.ron");
+        assert_eq!(
+            relative_sound_path(Path::new("/media/c.wav"), scene_path),
+            Path::new("/media/c.wav")
+        );
+    }
+
+    #[test]
+    fn moved_scene_round_trip() {
+        let dir = temp_dir("moved-scene");
+        let scene_path = dir.join("moved").join("scene.hibiki.ron");
+        fs::create_dir_all(scene_path.parent().unwrap()).unwrap();
+        // written somewhere else, the sounds moved along with the scene
+        fs::write(
+            &scene_path,
+            r#"(version: 1, entries: [
+                (sound_path: "sounds/a.wav", controller: Trigger, volume: 1.0, pan: 0.0, color: (1, 2, 3)),
+                (sound_path: "../shared/b.wav", controller: Trigger, volume: 1.0, pan: 0.0, color: (1, 2, 3)),
+            ])"#,
+        )
+        .unwrap();
+
+        let mut scene = Scene::load(&scene_path).unwrap();
+        let sound_dir = scene_dir(&scene_path);
+        assert_eq!(scene.entries[0].sound_path, sound_dir.join("sounds/a.wav"));
+        assert_eq!(
+            scene.entries[1].sound_path,
+            normalize(&sound_dir.join("../shared/b.wav"))
+        );
+
+        let loaded: Vec<PathBuf> = scene
+            .entries
+            .iter()
+            .map(|entry| entry.sound_path.clone())
+            .collect();
+        for entry in scene.entries.iter_mut() {
+            entry.sound_path = relative_sound_path(&entry.sound_path, &scene_path);
+        }
+        assert_eq!(scene.entries[0].sound_path, Path::new("sounds/a.wav"));
+        assert_eq!(scene.entries[1].sound_path, Path::new("../shared/b.wav"));
+        scene.save(&scene_path).unwrap();
+
+        let reloaded = Scene::load(&scene_path).unwrap();
+        let reloaded: Vec<PathBuf> = reloaded
+            .entries
+            .iter()
+            .map(|entry| entry.sound_path.clone())
+            .collect();
+        assert_eq!(reloaded, loaded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const ENTRY: &str =
+        r#"(sound_path: "a.wav", controller: Toggle, volume: 0.5, pan: -1.0, color: (1, 2, 3))"#;
+
+    fn load_text(name: &str, text: &str) -> Result<Scene, HibikiError> {
+        let dir = temp_dir(name);
+        let scene_path = dir.join("scene.hibiki.ron");
+        fs::write(&scene_path, text).unwrap();
+        let scene = Scene::load(&scene_path);
+        fs::remove_dir_all(&dir).unwrap();
+        scene
+    }
+
+    #[test]
+    fn legacy_bare_list_gets_upgraded() {
+        let scene = load_text("legacy", &format!("[{ENTRY}]")).unwrap();
+        assert_eq!(scene.version, SCENE_VERSION);
+        assert_eq!(scene.master_volume, 1.0);
+        assert_eq!(scene.entries.len(), 1);
+        assert!(scene.entries[0].controller == SoundKind::Toggle);
+        assert_eq!(scene.entries[0].volume, 0.5);
+        assert!(scene.entries[0].sound_path.ends_with("a.wav"));
+    }
+
+    #[test]
+    fn unversioned_scene_gets_upgraded() {
+        let scene = load_text("unversioned", &format!("(entries: [{ENTRY}])")).unwrap();
+        assert_eq!(scene.version, SCENE_VERSION);
+        assert_eq!(scene.name, "");
+        assert_eq!(scene.entries.len(), 1);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let text = format!("(version: {}, entries: [])", SCENE_VERSION + 1);
+        assert!(matches!(
+            load_text("newer", &text),
+            Err(HibikiError::UnsupportedSceneVersion(version)) if version == SCENE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let scene = load_text(
+            "round-trip-source",
+            &format!("(version: 1, name: \"Show\", master_volume: 0.8, entries: [{ENTRY}])"),
+        )
+        .unwrap();
+        let dir = temp_dir("round-trip");
+        let scene_path = dir.join("scene.hibiki.ron");
+        scene.save(&scene_path).unwrap();
+        let loaded = Scene::load(&scene_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(loaded == scene);
+        assert_eq!(loaded.name, "Show");
+        assert_eq!(loaded.master_volume, 0.8);
+    }
+
+    #[test]
+    fn bundle_round_trip() {
+        let dir = temp_dir("bundle");
+        let show = dir.join("show");
+        fs::create_dir_all(&show).unwrap();
+        fs::write(show.join("a.wav"), b"not really audio").unwrap();
+        let scene_path = show.join("scene.hibiki.ron");
+        fs::write(
+            &scene_path,
+            format!(
+                "(version: {SCENE_VERSION}, entries: [{ENTRY}, {}])",
+                ENTRY.replace("a.wav", "gone.wav")
+            ),
+        )
+        .unwrap();
+        let mut scene = Scene::load(&scene_path).unwrap();
+        let sources: Vec<SoundSource> = scene
+            .entries
+            .iter()
+            .map(|entry| {
+                SoundSource::from_file(entry.sound_path.clone())
+                    .unwrap_or_else(|_| SoundSource::missing(entry.sound_path.clone()))
+            })
+            .collect();
+        // the board packs the scene for the bundle's path
+        let bundle_path = dir.join("show.hibiki");
+        for entry in scene.entries.iter_mut() {
+            entry.sound_path = relative_sound_path(&entry.sound_path, &bundle_path);
+        }
+        bundle::export(scene, sources.iter(), &bundle_path).unwrap();
+
+        let unpacked = dir.join("unpacked");
+        fs::create_dir_all(&unpacked).unwrap();
+        let imported = bundle::import(&bundle_path, &unpacked).unwrap();
+        let scene = Scene::load(&imported).unwrap();
+        assert_eq!(scene.entries[0].sound_path, unpacked.join("sounds/a.wav"));
+        assert_eq!(
+            fs::read(&scene.entries[0].sound_path).unwrap(),
+            b"not really audio"
+        );
+        // missing sounds still point at where they used to be
+        assert_eq!(scene.entries[1].sound_path, show.join("gone.wav"));
+
+        // only empty folders get bundles unpacked into them
+        assert!(matches!(
+            bundle::import(&bundle_path, &unpacked),
+            Err(HibikiError::DirectoryNotEmpty(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Settings of the app itself, persisted through eframe's storage
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Name of the output device, `None` follows the system default
+    pub output_device: Option<String>,
+    /// Name of the monitor device, `None` disables monitoring
+    pub monitor_device: Option<String>,
+    pub monitor_volume: f32,
+    /// Recently used scenes, the most recent one is first
+    pub recent_scenes: Vec<PathBuf>,
+    /// Whether unsaved changes get written to a sidecar file periodically
+    pub autosave: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            monitor_device: None,
+            monitor_volume: 1.,
+            recent_scenes: vec![],
+            autosave: true,
+        }
+    }
+}
@@ -4,29 +4,343 @@ use std::{
     io::{BufReader, Cursor, Read},
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
-use eframe::epaint::Color32;
-use rodio::{Decoder, Sink};
+use eframe::{
+    egui::{self, Key},
+    epaint::Color32,
+};
+use rodio::{Decoder, Sink, Source};
 use serde::{Deserialize, Serialize};
 
-use crate::error::HibikiError;
+use crate::{
+    audio::{Outputs, Route},
+    duck::{DuckControl, Ducked, Ducking},
+    error::HibikiError,
+    fade::{FadeControl, Fading},
+    layout::PadSize,
+    midi::MidiMapping,
+    pan::{PanControl, Panned},
+    progress::{Progress, Tracked},
+    trigger::PadStatus,
+    trim::{BoxedSource, Trim},
+    waveform::PeaksHandle,
+};
 
 pub struct Sound {
     pub kind: SoundKind,
     pub source: SoundSource,
-    /// Sink of the audio, unused by `Trigger`
-    pub sink: Sink,
+    /// Playing instances of this sound, the most recent one is last
+    pub voices: Vec<Voice>,
     /// Used by `Activating` for storing its state
     pub state: bool,
     pub volume: f64,
     pub pan: f64,
+    /// Live pan position of everything this sound plays
+    pub panner: PanControl,
+    pub color: Color32,
+    /// Name shown on the pad, defaults to the file stem
+    pub name: String,
+    /// Optional emoji shown on the pad
+    pub icon: String,
+    pub hotkey: Option<Key>,
+    pub midi: MidiMapping,
+    pub size: PadSize,
+    /// Empty grid slots in front of this sound
+    pub gap: usize,
+    pub route: Route,
+    pub trim: Trim,
+    /// Seconds it takes a voice to reach full volume
+    pub fade_in: f64,
+    /// Seconds it takes a voice to go silent when it gets stopped
+    pub fade_out: f64,
+    /// Starting this sound stops the other sounds with the same group, empty for none
+    pub choke_group: String,
+    pub ducking: Ducking,
+    /// Live ducking state of everything this sound plays
+    pub ducker: DuckControl,
+}
+
+/// A single playing instance of a sound on its own sinks, so it can be stopped at any time
+pub struct Voice {
+    main: Option<Sink>,
+    monitor: Option<Sink>,
+    fade: FadeControl,
+    progress: Progress,
+    looping: bool,
+}
+
+impl Voice {
+    fn sinks(&self) -> impl Iterator<Item = &Sink> {
+        self.main.iter().chain(&self.monitor)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.sinks().any(|sink| !sink.empty())
+    }
+}
+
+/// Everything about a sound the user can edit, used to undo edits
+#[derive(Clone, PartialEq)]
+pub struct SoundSettings {
+    pub kind: SoundKind,
+    pub volume: f64,
+    pub pan: f64,
     pub color: Color32,
+    pub name: String,
+    pub icon: String,
+    pub hotkey: Option<Key>,
+    pub midi: MidiMapping,
+    pub size: PadSize,
+    pub gap: usize,
+    pub route: Route,
+    pub trim: Trim,
+    pub fade_in: f64,
+    pub fade_out: f64,
+    pub choke_group: String,
+    pub ducking: Ducking,
 }
 
+impl Sound {
+    /// Creates a sound with default settings
+    pub fn new(source: SoundSource) -> Self {
+        Self {
+            kind: SoundKind::Trigger,
+            name: source.file_stem(),
+            icon: String::new(),
+            source,
+            voices: vec![],
+            state: false,
+            volume: 1.0,
+            pan: 0.0,
+            panner: PanControl::default(),
+            color: catppuccin_egui::MACCHIATO.surface1,
+            hotkey: None,
+            midi: MidiMapping::default(),
+            size: PadSize::default(),
+            gap: 0,
+            route: Route::default(),
+            trim: Trim::default(),
+            fade_in: 0.,
+            fade_out: 0.,
+            choke_group: String::new(),
+            ducking: Ducking::default(),
+            ducker: DuckControl::default(),
+        }
+    }
+
+    pub fn settings(&self) -> SoundSettings {
+        SoundSettings {
+            kind: self.kind,
+            volume: self.volume,
+            pan: self.pan,
+            color: self.color,
+            name: self.name.clone(),
+            icon: self.icon.clone(),
+            hotkey: self.hotkey,
+            midi: self.midi,
+            size: self.size,
+            gap: self.gap,
+            route: self.route,
+            trim: self.trim,
+            fade_in: self.fade_in,
+            fade_out: self.fade_out,
+            choke_group: self.choke_group.clone(),
+            ducking: self.ducking,
+        }
+    }
+
+    /// Replaces the settings and returns the old ones
+    pub fn replace_settings(&mut self, settings: SoundSettings) -> SoundSettings {
+        let old = self.settings();
+        self.kind = settings.kind;
+        self.volume = settings.volume;
+        self.pan = settings.pan;
+        self.color = settings.color;
+        self.name = settings.name;
+        self.icon = settings.icon;
+        self.hotkey = settings.hotkey;
+        self.midi = settings.midi;
+        self.size = settings.size;
+        self.gap = settings.gap;
+        self.route = settings.route;
+        self.trim = settings.trim;
+        self.fade_in = settings.fade_in;
+        self.fade_out = settings.fade_out;
+        self.choke_group = settings.choke_group;
+        self.ducking = settings.ducking;
+        old
+    }
+
+    /// Volume of the main sink
+    fn gain(&self, outputs: &Outputs) -> f32 {
+        self.volume as f32 * outputs.master_volume
+    }
+
+    /// Decodes the sound and routes it through panning, ducking and fading
+    fn voice_source(
+        &self,
+        repeat: bool,
+        fade: &FadeControl,
+    ) -> Result<Fading<Ducked<Panned<BoxedSource>>>, HibikiError> {
+        let source = self.trim.apply(
+            || Ok(self.source.decoder()?.convert_samples::<f32>()),
+            repeat,
+        )?;
+        Ok(Fading::new(
+            Ducked::new(
+                Panned::new(source, self.panner.clone()),
+                self.ducker.clone(),
+            ),
+            fade.clone(),
+            Duration::from_secs_f64(self.fade_in.max(0.)),
+        ))
+    }
+
+    /// Starts a new voice on every output of its route
+    pub fn start(&mut self, outputs: &Outputs, repeat: bool) -> Result<(), HibikiError> {
+        let main = outputs.main().filter(|_| self.route.main());
+        let monitor = outputs.monitor().filter(|_| self.route.monitor());
+        if main.is_none() && monitor.is_none() {
+            return Err(HibikiError::NoAudioDevice);
+        }
+        let mut voice = Voice {
+            main: None,
+            monitor: None,
+            fade: FadeControl::default(),
+            progress: Progress::default(),
+            looping: repeat,
+        };
+        if let Some(handle) = main {
+            let sink = Sink::try_new(handle).map_err(HibikiError::PlaybackError)?;
+            sink.set_volume(self.gain(outputs));
+            sink.append(Tracked::new(
+                self.voice_source(repeat, &voice.fade)?,
+                voice.progress.clone(),
+            ));
+            voice.main = Some(sink);
+        }
+        if let Some(handle) = monitor {
+            let sink = Sink::try_new(handle).map_err(HibikiError::PlaybackError)?;
+            sink.set_volume(self.gain(outputs) * outputs.monitor_volume);
+            let source = self.voice_source(repeat, &voice.fade)?;
+            // only one of the sinks reports the progress
+            if voice.main.is_some() {
+                sink.append(source);
+            } else {
+                sink.append(Tracked::new(source, voice.progress.clone()));
+            }
+            voice.monitor = Some(sink);
+        }
+        self.voices.push(voice);
+        Ok(())
+    }
+
+    /// Stops every voice, fading them out if a duration is given, and resets the toggle state
+    pub fn stop(&mut self, fade_out: Option<Duration>) {
+        for voice in &self.voices {
+            match fade_out {
+                Some(duration) => voice.fade.fade_out(duration),
+                None => voice.sinks().for_each(Sink::stop),
+            }
+        }
+        self.state = false;
+    }
+
+    /// Stops what the voices play on the monitor output, the main output keeps playing
+    pub fn cut_monitor(&mut self) {
+        for voice in self.voices.iter_mut() {
+            if let Some(sink) = voice.monitor.take() {
+                sink.stop();
+            }
+        }
+    }
+
+    /// Fade-out used when the sound gets stopped, if it has one
+    pub fn fade_out(&self) -> Option<Duration> {
+        (self.fade_out > 0.).then(|| Duration::from_secs_f64(self.fade_out))
+    }
+
+    /// Applies live edits to the playing voices and forgets the finished ones
+    pub fn sync(&mut self, outputs: &Outputs) {
+        self.voices.retain(Voice::is_playing);
+        for voice in &self.voices {
+            if let Some(sink) = &voice.main {
+                sink.set_volume(self.gain(outputs));
+            }
+            if let Some(sink) = &voice.monitor {
+                sink.set_volume(self.gain(outputs) * outputs.monitor_volume);
+            }
+        }
+        self.panner.set(self.pan);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.voices.iter().any(Voice::is_playing)
+    }
+
+    fn latest_voice(&self) -> Option<&Voice> {
+        self.voices.iter().rev().find(|voice| voice.is_playing())
+    }
+
+    /// Position of the most recent voice in the file in seconds
+    pub fn playhead(&self) -> Option<f64> {
+        let voice = self.latest_voice()?;
+        let duration = self.source.duration()?.as_secs_f64();
+        Some(self.trim.position(
+            voice.progress.elapsed().as_secs_f64(),
+            voice.looping,
+            duration,
+        ))
+    }
+
+    pub fn status(&self) -> PadStatus {
+        if self.source.broken {
+            return PadStatus {
+                broken: true,
+                ..Default::default()
+            };
+        }
+        let Some(voice) = self.latest_voice() else {
+            return PadStatus::default();
+        };
+        let armed = matches!(self.kind, SoundKind::Toggle | SoundKind::ToggleRepeat) && self.state;
+        // progress and remaining time refer to the trimmed part of the file
+        let position = self
+            .source
+            .duration()
+            .zip(self.playhead())
+            .map(|(duration, playhead)| {
+                let (start, end) = self.trim.range(duration.as_secs_f64());
+                (playhead - start, end - start)
+            });
+        PadStatus {
+            playing: true,
+            looping: voice.looping,
+            armed,
+            progress: position
+                .filter(|(_, length)| *length > 0.)
+                .map(|(played, length)| (played / length) as f32),
+            remaining: position
+                .map(|(played, length)| Duration::from_secs_f64((length - played).max(0.))),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SoundSource {
     pub path: PathBuf,
     pub data: Arc<[u8]>,
+    /// Length reported by the decoder, some formats only know it once decoded
+    pub reported_duration: Option<Duration>,
+    /// Whether the data can't be decoded, playing it fails
+    pub broken: bool,
+    /// Whether the file wasn't found, the sound needs to be relinked
+    pub missing: bool,
+    /// Waveform summary, computed on first use
+    pub peaks: PeaksHandle,
 }
 
 impl SoundSource {
@@ -43,15 +357,63 @@ impl SoundSource {
         reader
             .read_to_end(&mut bytes)
             .map_err(HibikiError::InternalError)?;
+        let data: Arc<[u8]> = bytes.into();
+        let decoder = Decoder::new(Cursor::new(data.clone()));
         Ok(Self {
             path,
-            data: bytes.into(),
+            broken: decoder.is_err(),
+            reported_duration: decoder.ok().and_then(|decoder| decoder.total_duration()),
+            missing: false,
+            peaks: PeaksHandle::default(),
+            data,
+        })
+    }
+
+    /// Placeholder for a file that doesn't exist, so its sound keeps its place and settings
+    pub fn missing(path: PathBuf) -> SoundSource {
+        Self {
+            path,
+            data: Arc::new([]),
+            reported_duration: None,
+            broken: true,
+            missing: true,
+            peaks: PeaksHandle::default(),
+        }
+    }
+
+    /// Length of the audio, falls back to the length measured along with the peaks
+    pub fn duration(&self) -> Option<Duration> {
+        self.reported_duration.or_else(|| {
+            let length = self.peaks.ready()?.duration();
+            Some(Duration::from_secs_f64(length))
         })
     }
 
-    pub fn decoder(&self) -> Decoder<Cursor<Arc<[u8]>>> {
-        let source = Decoder::new(Cursor::new(self.data.clone()));
-        source.unwrap()
+    /// Starts measuring the length in the background if the decoder doesn't report it
+    pub fn measure_duration(&self, ctx: &egui::Context) {
+        if self.reported_duration.is_none() && !self.missing && !self.broken {
+            self.peaks.get(&self.data, ctx);
+        }
+    }
+
+    pub fn file_stem(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Fails with the decoder's error if the data can't be decoded
+    pub fn validate(&self) -> Result<(), HibikiError> {
+        self.decoder().map(|_| ())
+    }
+
+    pub fn decoder(&self) -> Result<Decoder<Cursor<Arc<[u8]>>>, HibikiError> {
+        if self.missing {
+            return Err(HibikiError::DoesNotExist(self.path.clone()));
+        }
+        Decoder::new(Cursor::new(self.data.clone()))
+            .map_err(|err| HibikiError::UndecodableAudio(self.path.clone(), err))
     }
 }
 
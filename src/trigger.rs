@@ -1,16 +1,130 @@
+use std::time::Duration;
+
 use eframe::{
-    egui::{CursorIcon, Response, Sense, Ui, Widget},
-    epaint::{Color32, Vec2},
+    egui::{Align2, CursorIcon, Response, Sense, Ui, Widget},
+    epaint::{Color32, FontId, Rect, Rounding, Stroke, Vec2},
 };
 
-pub struct Trigger {
+pub struct Trigger<'a> {
     pub color: Color32,
+    pub size: Vec2,
+    pub label: &'a str,
+    /// Optional emoji shown above the label
+    pub icon: &'a str,
+    pub status: PadStatus,
+}
+
+#[derive(Default)]
+pub struct PadStatus {
+    pub playing: bool,
+    pub looping: bool,
+    /// Toggled on and playing
+    pub armed: bool,
+    /// Played fraction of the sound
+    pub progress: Option<f32>,
+    pub remaining: Option<Duration>,
+    /// The sound can't be played
+    pub broken: bool,
+}
+
+/// Picks a text color that stays readable on the given background
+fn contrast_color(background: Color32) -> Color32 {
+    let luminance = 0.299 * background.r() as f32
+        + 0.587 * background.g() as f32
+        + 0.114 * background.b() as f32;
+    if luminance > 150. {
+        catppuccin_egui::MACCHIATO.crust
+    } else {
+        catppuccin_egui::MACCHIATO.text
+    }
 }
 
-impl Widget for Trigger {
+impl Widget for Trigger<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
-        let (rect, response) = ui.allocate_at_least(Vec2::splat(50.), Sense::click());
-        ui.painter().rect_filled(rect, 10., self.color);
+        let (rect, response) = ui.allocate_at_least(self.size, Sense::click());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 10., self.color);
+
+        let text_color = contrast_color(self.color);
+        let label_font = FontId::proportional(11.);
+        if self.icon.is_empty() {
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                self.label,
+                label_font,
+                text_color,
+            );
+        } else {
+            painter.text(
+                rect.center(),
+                Align2::CENTER_BOTTOM,
+                self.icon,
+                FontId::proportional(rect.height().min(rect.width()) * 0.35),
+                text_color,
+            );
+            painter.text(
+                rect.center() + Vec2::new(0., 2.),
+                Align2::CENTER_TOP,
+                self.label,
+                label_font,
+                text_color,
+            );
+        }
+
+        if self.status.playing {
+            let accent = if self.status.armed {
+                catppuccin_egui::MACCHIATO.peach
+            } else {
+                catppuccin_egui::MACCHIATO.green
+            };
+            painter.rect_stroke(rect.shrink(1.), 10., Stroke::new(2., accent));
+            if let Some(progress) = self.status.progress {
+                let bar = Rect::from_min_max(
+                    rect.left_bottom() + Vec2::new(6., -6.),
+                    rect.right_bottom() + Vec2::new(-6., -3.),
+                );
+                painter.rect_filled(bar, Rounding::same(1.5), text_color.gamma_multiply(0.3));
+                let mut played = bar;
+                played.set_right(bar.left() + bar.width() * progress.clamp(0., 1.));
+                painter.rect_filled(played, Rounding::same(1.5), accent);
+            }
+            let corner_font = FontId::proportional(9.);
+            if let Some(remaining) = self.status.remaining {
+                let seconds = remaining.as_secs();
+                painter.text(
+                    rect.right_top() + Vec2::new(-5., 3.),
+                    Align2::RIGHT_TOP,
+                    format!("-{}:{:02}", seconds / 60, seconds % 60),
+                    corner_font.clone(),
+                    text_color,
+                );
+            }
+            if self.status.looping {
+                painter.text(
+                    rect.left_top() + Vec2::new(5., 3.),
+                    Align2::LEFT_TOP,
+                    "🔁",
+                    corner_font,
+                    text_color,
+                );
+            }
+        }
+
+        if self.status.broken {
+            painter.rect_stroke(
+                rect.shrink(1.),
+                10.,
+                Stroke::new(2., catppuccin_egui::MACCHIATO.red),
+            );
+            painter.text(
+                rect.left_top() + Vec2::new(5., 3.),
+                Align2::LEFT_TOP,
+                "⚠",
+                FontId::proportional(12.),
+                catppuccin_egui::MACCHIATO.red,
+            );
+        }
 
         if response.hovered {
             ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
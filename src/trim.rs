@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use rodio::{queue, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::error::HibikiError;
+
+pub type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+/// Part of a file that gets played, all times are in seconds from the start of the file
+#[derive(Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct Trim {
+    pub start: f64,
+    /// `None` plays until the end of the file
+    pub end: Option<f64>,
+    /// Loop-in and loop-out point repeated by the repeating kinds instead of the whole sound
+    pub loop_region: Option<(f64, f64)>,
+}
+
+/// Skips to `start` and ends the source at `end`
+fn cut(
+    source: impl Source<Item = f32> + Send + 'static,
+    start: f64,
+    end: Option<f64>,
+) -> BoxedSource {
+    let source = source.skip_duration(Duration::from_secs_f64(start));
+    match end {
+        Some(end) => Box::new(source.take_duration(Duration::from_secs_f64(end - start))),
+        None => Box::new(source),
+    }
+}
+
+impl Trim {
+    fn start(&self) -> f64 {
+        self.start.max(0.)
+    }
+
+    /// End point that isn't before the start
+    fn end(&self) -> Option<f64> {
+        self.end.map(|end| end.max(self.start()))
+    }
+
+    /// Loop region that lies within the trimmed part
+    fn loop_region(&self) -> Option<(f64, f64)> {
+        let (loop_in, loop_out) = self.loop_region?;
+        let loop_out = self.end().map_or(loop_out, |end| loop_out.min(end));
+        let loop_in = loop_in.max(self.start());
+        (loop_in < loop_out).then_some((loop_in, loop_out))
+    }
+
+    /// Trims the source, repeating sources play up to the loop-out point and then repeat the loop region.
+    /// `decode` gets called for each part of the sound that needs its own decoder.
+    pub fn apply<S>(
+        &self,
+        mut decode: impl FnMut() -> Result<S, HibikiError>,
+        repeat: bool,
+    ) -> Result<BoxedSource, HibikiError>
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let start = self.start();
+        if !repeat {
+            return Ok(cut(decode()?, start, self.end()));
+        }
+        let Some((loop_in, loop_out)) = self.loop_region() else {
+            return Ok(Box::new(
+                cut(decode()?, start, self.end()).repeat_infinite(),
+            ));
+        };
+        let (input, output) = queue::queue(false);
+        input.append(cut(decode()?, start, Some(loop_out)));
+        input.append(cut(decode()?, loop_in, Some(loop_out)).repeat_infinite());
+        Ok(Box::new(output))
+    }
+
+    /// Position in the file after playing for `elapsed` seconds
+    pub fn position(&self, elapsed: f64, looping: bool, duration: f64) -> f64 {
+        let (start, end) = self.range(duration);
+        if !looping {
+            return (start + elapsed).min(end);
+        }
+        match self.loop_region() {
+            Some((loop_in, loop_out)) if start + elapsed >= loop_out => {
+                loop_in + (start + elapsed - loop_out) % (loop_out - loop_in)
+            }
+            Some(_) => start + elapsed,
+            None if end > start => start + elapsed % (end - start),
+            None => start,
+        }
+    }
+
+    /// Played range of a file with the given duration
+    pub fn range(&self, duration: f64) -> (f64, f64) {
+        let start = self.start().min(duration);
+        (start, self.end().unwrap_or(duration).clamp(start, duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    /// Mono sound of two seconds at 4 samples per second, each sample is its own index.
+    /// End points lie between samples, so rounding inside rodio doesn't matter.
+    fn decode() -> Result<SamplesBuffer<f32>, HibikiError> {
+        Ok(SamplesBuffer::new(
+            1,
+            4,
+            (0..8).map(|i| i as f32).collect::<Vec<_>>(),
+        ))
+    }
+
+    fn played(trim: Trim, repeat: bool, count: usize) -> Vec<f32> {
+        trim.apply(decode, repeat).unwrap().take(count).collect()
+    }
+
+    #[test]
+    fn without_loop() {
+        let trim = Trim {
+            start: 0.5,
+            end: Some(1.625),
+            loop_region: None,
+        };
+        assert_eq!(played(trim, false, 10), [2., 3., 4., 5.]);
+        assert_eq!(trim.position(0.5, false, 2.), 1.);
+        // stays at the end once played
+        assert_eq!(trim.position(3., false, 2.), 1.625);
+    }
+
+    #[test]
+    fn loop_without_region() {
+        let trim = Trim {
+            start: 0.5,
+            end: Some(1.625),
+            loop_region: None,
+        };
+        assert_eq!(
+            played(trim, true, 10),
+            [2., 3., 4., 5., 2., 3., 4., 5., 2., 3.]
+        );
+        assert_eq!(trim.position(1.25, true, 2.), 0.625);
+    }
+
+    #[test]
+    fn loop_with_region() {
+        let trim = Trim {
+            start: 0.25,
+            end: Some(1.875),
+            loop_region: Some((0.75, 1.375)),
+        };
+        assert_eq!(
+            played(trim, true, 10),
+            [1., 2., 3., 4., 3., 4., 3., 4., 3., 4.]
+        );
+        assert_eq!(trim.position(0.5, true, 2.), 0.75);
+        // wraps from loop-out back to loop-in
+        assert_eq!(trim.position(1.125, true, 2.), 0.75);
+        assert_eq!(trim.position(1.375, true, 2.), 1.);
+        // the region only matters when repeating
+        assert_eq!(played(trim, false, 10), [1., 2., 3., 4., 5., 6.]);
+    }
+
+    #[test]
+    fn unknown_end() {
+        let trim = Trim {
+            start: 1.,
+            end: None,
+            loop_region: None,
+        };
+        assert_eq!(played(trim, false, 10), [4., 5., 6., 7.]);
+        assert_eq!(played(trim, true, 6), [4., 5., 6., 7., 4., 5.]);
+        assert_eq!(trim.range(2.), (1., 2.));
+        assert_eq!(trim.position(1.5, true, 2.), 1.5);
+    }
+
+    #[test]
+    fn start_past_the_duration() {
+        let trim = Trim {
+            start: 3.,
+            end: None,
+            loop_region: None,
+        };
+        assert_eq!(played(trim, false, 10), []);
+        assert_eq!(trim.range(2.), (2., 2.));
+        assert_eq!(trim.position(1., false, 2.), 2.);
+        assert_eq!(trim.position(1., true, 2.), 2.);
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::error::HibikiError;
+
+/// Time without further events before a file counts as changed, so half-written files are skipped
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Reports files that got changed by other applications
+pub struct Watcher {
+    ctx: egui::Context,
+    watcher: RecommendedWatcher,
+    receiver: Receiver<PathBuf>,
+    /// Watched directories, files get replaced by renames so watching them directly misses changes
+    dirs: HashSet<PathBuf>,
+    /// Changed paths and the time of their last event
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Watcher {
+    pub fn new(ctx: egui::Context) -> Result<Self, HibikiError> {
+        let (sender, receiver) = mpsc::channel();
+        let repaint = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+                repaint.request_repaint();
+            }
+        })
+        .map_err(HibikiError::WatchError)?;
+        Ok(Self {
+            ctx,
+            watcher,
+            receiver,
+            dirs: HashSet::new(),
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Watches the directories containing the given files and stops watching all others
+    pub fn watch<'a>(&mut self, files: impl Iterator<Item = &'a Path>) -> Result<(), HibikiError> {
+        let dirs: HashSet<PathBuf> = files.filter_map(Path::parent).map(Path::to_owned).collect();
+        if dirs == self.dirs {
+            return Ok(());
+        }
+        for dir in self.dirs.difference(&dirs) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        let mut result = Ok(());
+        // missing directories are skipped, missing files get reported elsewhere
+        for dir in dirs.difference(&self.dirs).filter(|dir| dir.is_dir()) {
+            if let Err(err) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                result = Err(HibikiError::WatchError(err));
+            }
+        }
+        // failed directories aren't retried until the set changes, so errors don't repeat every frame
+        self.dirs = dirs;
+        result
+    }
+
+    /// Paths that changed and settled since the last call
+    pub fn changes(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for path in self.receiver.try_iter() {
+            self.pending.insert(path, now);
+        }
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last_event)| now - **last_event >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        if !self.pending.is_empty() {
+            self.ctx.request_repaint_after(SETTLE_TIME);
+        }
+        settled
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc, OnceLock,
+    },
+    thread,
+};
+
+use eframe::{
+    egui::{self, Align2, CursorIcon, Id, Response, Sense, Ui, Widget},
+    epaint::{Color32, FontId, Rect, Stroke, Vec2},
+};
+use rodio::{Decoder, Source};
+
+use crate::trim::Trim;
+
+/// Resolution of the peak summary
+const BUCKETS_PER_SECOND: u32 = 200;
+/// Distance in points at which a marker can be grabbed
+const GRAB_DISTANCE: f32 = 6.;
+
+/// Minimum and maximum sample of short, consecutive parts of a sound
+pub struct Peaks {
+    buckets: Vec<(f32, f32)>,
+    /// Length of a bucket in seconds
+    bucket_duration: f64,
+    /// Length of the whole sound in seconds
+    duration: f64,
+}
+
+impl Peaks {
+    fn compute(data: Arc<[u8]>) -> Option<Self> {
+        let decoder = Decoder::new(Cursor::new(data)).ok()?;
+        let sample_rate = decoder.sample_rate().max(1);
+        let frames_per_bucket = (sample_rate / BUCKETS_PER_SECOND).max(1);
+        let channels = decoder.channels().max(1) as usize;
+        let samples_per_bucket = frames_per_bucket as usize * channels;
+        let mut buckets = vec![];
+        let mut bucket = (0f32, 0f32);
+        let mut samples = 0;
+        for sample in decoder.convert_samples::<f32>() {
+            bucket = (bucket.0.min(sample), bucket.1.max(sample));
+            samples += 1;
+            if samples % samples_per_bucket == 0 {
+                buckets.push(bucket);
+                bucket = (0., 0.);
+            }
+        }
+        buckets.push(bucket);
+        Some(Self {
+            buckets,
+            bucket_duration: frames_per_bucket as f64 / sample_rate as f64,
+            duration: samples as f64 / (sample_rate as f64 * channels as f64),
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Lowest and highest sample between two points in time
+    fn range(&self, from: f64, to: f64) -> Option<(f32, f32)> {
+        let first = (from / self.bucket_duration).max(0.) as usize;
+        let last = ((to / self.bucket_duration).ceil() as usize)
+            .max(first + 1)
+            .min(self.buckets.len());
+        self.buckets
+            .get(first..last)?
+            .iter()
+            .copied()
+            .reduce(|(min, max), (low, high)| (min.min(low), max.max(high)))
+    }
+}
+
+/// Sound data whose peaks are wanted, along with where to put them
+struct PeaksJob {
+    peaks: Arc<OnceLock<Option<Peaks>>>,
+    data: Arc<[u8]>,
+    ctx: egui::Context,
+}
+
+/// Queue of the single thread computing peaks, so loading a large board doesn't decode every file at once
+fn peaks_worker() -> &'static Sender<PeaksJob> {
+    static WORKER: OnceLock<Sender<PeaksJob>> = OnceLock::new();
+    WORKER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<PeaksJob>();
+        thread::spawn(move || {
+            for job in receiver {
+                let _ = job.peaks.set(Peaks::compute(job.data));
+                job.ctx.request_repaint();
+            }
+        });
+        sender
+    })
+}
+
+/// Peaks of a sound that get computed on a background thread the first time they are needed
+#[derive(Clone, Default)]
+pub struct PeaksHandle {
+    peaks: Arc<OnceLock<Option<Peaks>>>,
+    started: Arc<AtomicBool>,
+}
+
+impl PeaksHandle {
+    /// Returns the peaks once they are ready, `data` has to stay the same for a handle
+    pub fn get(&self, data: &Arc<[u8]>, ctx: &egui::Context) -> Option<&Peaks> {
+        if !self.started.swap(true, Ordering::Relaxed) {
+            let _ = peaks_worker().send(PeaksJob {
+                peaks: self.peaks.clone(),
+                data: data.clone(),
+                ctx: ctx.clone(),
+            });
+        }
+        self.ready()
+    }
+
+    /// Returns the peaks if they have been computed already
+    pub fn ready(&self) -> Option<&Peaks> {
+        self.peaks.get().and_then(Option::as_ref)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Marker {
+    Start,
+    End,
+    LoopIn,
+    LoopOut,
+}
+
+/// Zoomable waveform with draggable trim and loop markers
+pub struct Waveform<'a> {
+    pub peaks: Option<&'a Peaks>,
+    pub trim: &'a mut Trim,
+    /// Current position of the sound in seconds
+    pub playhead: Option<f64>,
+}
+
+impl Waveform<'_> {
+    fn markers(&self, duration: f64) -> Vec<(Marker, f64, Color32)> {
+        let mut markers = vec![
+            (
+                Marker::Start,
+                self.trim.start,
+                catppuccin_egui::MACCHIATO.green,
+            ),
+            (
+                Marker::End,
+                self.trim.end.unwrap_or(duration),
+                catppuccin_egui::MACCHIATO.red,
+            ),
+        ];
+        if let Some((loop_in, loop_out)) = self.trim.loop_region {
+            markers.push((Marker::LoopIn, loop_in, catppuccin_egui::MACCHIATO.blue));
+            markers.push((Marker::LoopOut, loop_out, catppuccin_egui::MACCHIATO.blue));
+        }
+        markers
+    }
+
+    /// Moves the marker, keeping the markers in order
+    fn set_marker(&mut self, marker: Marker, time: f64, duration: f64) {
+        let trim = &mut *self.trim;
+        let end = trim.end.unwrap_or(duration);
+        match marker {
+            // max before min, so markers that are out of order don't panic like clamp would
+            Marker::Start => trim.start = time.min(end).max(0.),
+            Marker::End => trim.end = Some(time.min(duration).max(trim.start)),
+            Marker::LoopIn => {
+                if let Some((loop_in, loop_out)) = &mut trim.loop_region {
+                    *loop_in = time.min(*loop_out).max(trim.start);
+                }
+            }
+            Marker::LoopOut => {
+                if let Some((loop_in, loop_out)) = &mut trim.loop_region {
+                    *loop_out = time.min(end).max(*loop_in);
+                }
+            }
+        }
+    }
+}
+
+impl Widget for Waveform<'_> {
+    fn ui(mut self, ui: &mut Ui) -> Response {
+        let size = Vec2::new(320., 80.);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 4., catppuccin_egui::MACCHIATO.mantle);
+
+        let Some(peaks) = self.peaks.filter(|peaks| peaks.duration() > 0.) else {
+            let text = if self.peaks.is_some() {
+                "No audio"
+            } else {
+                "Loading waveform..."
+            };
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                text,
+                FontId::proportional(12.),
+                catppuccin_egui::MACCHIATO.overlay0,
+            );
+            return response;
+        };
+        let duration = peaks.duration();
+
+        // visible part of the sound as offset and length in seconds
+        let view_id = response.id;
+        let (mut offset, mut visible) = ui
+            .data(|data| data.get_temp::<(f64, f64)>(view_id))
+            .unwrap_or((0., duration));
+        let min_visible = duration.min(0.01);
+        let fraction = |x: f32| ((x - rect.left()) / rect.width()) as f64;
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta);
+            let zoom = (-scroll.y as f64 / 200.).exp();
+            let anchor = offset + visible * fraction(pointer.x);
+            visible = (visible * zoom).clamp(min_visible, duration);
+            offset = anchor - visible * fraction(pointer.x);
+            offset -= scroll.x as f64 / rect.width() as f64 * visible;
+        }
+        // the sound might have been replaced by a shorter one
+        visible = visible.clamp(min_visible, duration);
+
+        let marker_id = Id::new((view_id, "marker"));
+        let mut grabbed = ui.data(|data| data.get_temp::<Option<Marker>>(marker_id).flatten());
+        let to_x = |time: f64, offset: f64, visible: f64| {
+            rect.left() + ((time - offset) / visible) as f32 * rect.width()
+        };
+        if response.drag_started() {
+            grabbed = response.interact_pointer_pos().and_then(|pointer| {
+                self.markers(duration)
+                    .into_iter()
+                    .map(|(marker, time, _)| {
+                        (marker, (to_x(time, offset, visible) - pointer.x).abs())
+                    })
+                    .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(marker, _)| marker)
+            });
+            ui.data_mut(|data| data.insert_temp(marker_id, grabbed));
+        }
+        if response.dragged() {
+            match (grabbed, response.interact_pointer_pos()) {
+                (Some(marker), Some(pointer)) => {
+                    self.set_marker(marker, offset + visible * fraction(pointer.x), duration);
+                    response.mark_changed();
+                }
+                // dragging the background pans the view
+                _ => offset -= response.drag_delta().x as f64 / rect.width() as f64 * visible,
+            }
+        }
+        offset = offset.clamp(0., duration - visible);
+        ui.data_mut(|data| data.insert_temp(view_id, (offset, visible)));
+        let to_x = |time: f64| to_x(time, offset, visible);
+
+        let hovered_marker = response.hover_pos().is_some_and(|pointer| {
+            self.markers(duration)
+                .iter()
+                .any(|(_, time, _)| (to_x(*time) - pointer.x).abs() <= GRAB_DISTANCE)
+        });
+        if hovered_marker || (grabbed.is_some() && response.dragged()) {
+            ui.ctx().set_cursor_icon(CursorIcon::ResizeHorizontal);
+        }
+
+        if let Some((loop_in, loop_out)) = self.trim.loop_region {
+            painter.rect_filled(
+                Rect::from_x_y_ranges(to_x(loop_in)..=to_x(loop_out), rect.y_range()),
+                0.,
+                catppuccin_egui::MACCHIATO.blue.gamma_multiply(0.15),
+            );
+        }
+        let end = self.trim.end.unwrap_or(duration);
+        let column_duration = visible / rect.width() as f64;
+        for column in 0..rect.width() as usize {
+            let from = offset + column as f64 * column_duration;
+            let Some((min, max)) = peaks.range(from, from + column_duration) else {
+                continue;
+            };
+            let color = if (self.trim.start..=end).contains(&from) {
+                catppuccin_egui::MACCHIATO.text
+            } else {
+                catppuccin_egui::MACCHIATO.overlay0
+            };
+            let x = rect.left() + column as f32 + 0.5;
+            let half_height = rect.height() / 2.;
+            painter.line_segment(
+                [
+                    egui::pos2(x, rect.center().y - max * half_height),
+                    egui::pos2(x, rect.center().y - min * half_height + 1.),
+                ],
+                Stroke::new(1., color),
+            );
+        }
+        for (_, time, color) in self.markers(duration) {
+            let x = to_x(time);
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                Stroke::new(2., color),
+            );
+        }
+        if let Some(playhead) = self.playhead {
+            let x = to_x(playhead);
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                Stroke::new(1., catppuccin_egui::MACCHIATO.yellow),
+            );
+        }
+
+        response.on_hover_text("Scroll to zoom, drag the markers to trim")
+    }
+}